@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use crate::core::vec2::Vec2;
+
+pub const CELL_SIZE: f32 = 64.0;
+//? Bounds covering more cells than this are kept aside and checked against everything, so a huge floor does not fill the map.
+const MAX_CELLS: i64 = 256;
+
+#[derive(Clone, Copy)]
+pub struct Aabb {
+  pub min: Vec2,
+  pub max: Vec2,
+}
+
+impl Aabb {
+  pub fn overlaps(self, other: Aabb) -> bool {
+    self.min.get_x() <= other.max.get_x() && other.min.get_x() <= self.max.get_x()
+      && self.min.get_y() <= other.max.get_y() && other.min.get_y() <= self.max.get_y()
+  }
+
+  pub fn grown(self, by: f32) -> Aabb {
+    Aabb { min: self.min - Vec2::new(by, by), max: self.max + Vec2::new(by, by) }
+  }
+
+  pub fn union(self, other: Aabb) -> Aabb {
+    Aabb {
+      min: Vec2::new(self.min.get_x().min(other.min.get_x()), self.min.get_y().min(other.min.get_y())),
+      max: Vec2::new(self.max.get_x().max(other.max.get_x()), self.max.get_y().max(other.max.get_y())),
+    }
+  }
+}
+
+pub struct SpatialHash {
+  cell: f32,
+  cells: HashMap<(i32, i32), Vec<usize>>,
+  large: Vec<usize>,
+  bounds: Vec<Aabb>,
+}
+
+impl SpatialHash {
+  pub fn new(cell: f32) -> SpatialHash {
+    SpatialHash { cell, cells: HashMap::new(), large: Vec::new(), bounds: Vec::new() }
+  }
+
+  pub fn build(bounds: impl IntoIterator<Item = Aabb>, cell: f32) -> SpatialHash {
+    let mut hash: SpatialHash = SpatialHash::new(cell);
+    for aabb in bounds {
+      hash.insert(aabb);
+    }
+    hash
+  }
+
+  fn cell_range(&self, aabb: Aabb) -> (i32, i32, i32, i32) {
+    let to_cell = |value: f32| (value / self.cell).floor() as i32;
+    (to_cell(aabb.min.get_x()), to_cell(aabb.min.get_y()), to_cell(aabb.max.get_x()), to_cell(aabb.max.get_y()))
+  }
+
+  pub fn insert(&mut self, aabb: Aabb) -> usize {
+    let index: usize = self.bounds.len();
+    self.bounds.push(aabb);
+    let (min_x, min_y, max_x, max_y) = self.cell_range(aabb);
+    if (max_x as i64 - min_x as i64 + 1) * (max_y as i64 - min_y as i64 + 1) > MAX_CELLS {
+      self.large.push(index);
+      return index
+    }
+    for x in min_x..=max_x {
+      for y in min_y..=max_y {
+        self.cells.entry((x, y)).or_default().push(index);
+      }
+    }
+    index
+  }
+
+  pub fn query(&self, aabb: Aabb) -> Vec<usize> {
+    let mut found: Vec<usize> = self.large.clone();
+    let (min_x, min_y, max_x, max_y) = self.cell_range(aabb);
+    if (max_x as i64 - min_x as i64 + 1) * (max_y as i64 - min_y as i64 + 1) > MAX_CELLS {
+      found = (0..self.bounds.len()).collect();
+    } else {
+      for x in min_x..=max_x {
+        for y in min_y..=max_y {
+          if let Some(cell) = self.cells.get(&(x, y)) {
+            found.extend_from_slice(cell);
+          }
+        }
+      }
+    }
+    found.sort_unstable();
+    found.dedup();
+    found.retain(|index| self.bounds[*index].overlaps(aabb));
+    found
+  }
+
+  pub fn pairs(&self) -> Vec<(usize, usize)> {
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+    for cell in self.cells.values() {
+      for (i, a) in cell.iter().enumerate() {
+        for b in cell.iter().skip(i + 1) {
+          pairs.push((*a.min(b), *a.max(b)));
+        }
+      }
+    }
+    for large in self.large.iter() {
+      for other in (0..self.bounds.len()).filter(|other| other != large) {
+        pairs.push((*large.min(&other), *large.max(&other)));
+      }
+    }
+    pairs.sort_unstable();
+    pairs.dedup();
+    pairs.retain(|(a, b)| self.bounds[*a].overlaps(self.bounds[*b]));
+    pairs
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Instant;
+
+  use super::*;
+  use crate::core::{collision_layers::LayerMask, color::Color, nodes::{collider::Collider, node::Node, rectmesh::RectMesh}, proxy::{NodeHandle, Place}, shape::{Hull, Shape}, testing};
+
+  fn scatter(seed: &mut u64, range: f32) -> f32 {
+    *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    (*seed >> 33) as f32 / (1u64 << 31) as f32 * range
+  }
+
+  fn bullets(count: usize) -> Vec<Hull> {
+    let side: f32 = (count as f32).sqrt() * 40.0;
+    let mut seed: u64 = count as u64;
+    (0..count).map(|_| {
+      let center: Vec2 = Vec2::new(scatter(&mut seed, side), scatter(&mut seed, side));
+      Hull { points: vec![center], radius: 4.0 }
+    }).collect()
+  }
+
+  fn all_pairs(hulls: &[Hull]) -> Vec<(usize, usize)> {
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+    for (i, a) in hulls.iter().enumerate() {
+      for (j, b) in hulls.iter().enumerate().skip(i + 1) {
+        if a.overlaps(b) {
+          pairs.push((i, j));
+        }
+      }
+    }
+    pairs
+  }
+
+  fn hashed_pairs(hulls: &[Hull]) -> Vec<(usize, usize)> {
+    let hash: SpatialHash = SpatialHash::build(hulls.iter().map(|hull| hull.aabb()), CELL_SIZE);
+    hash.pairs().into_iter().filter(|(a, b)| hulls[*a].overlaps(&hulls[*b])).collect()
+  }
+
+  fn collider(hull: &Hull) -> NodeHandle {
+    let corner: Vec2 = hull.points[0] - Vec2::new(hull.radius, hull.radius);
+    let mut collider: Collider = Collider::new(corner, Vec2::new(hull.radius, hull.radius) * 2.0, LayerMask(1), LayerMask::EVERYTHING);
+    collider.shape = Shape::Circle { radius: hull.radius };
+    NodeHandle::new(collider)
+  }
+
+  fn millis(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+  }
+
+  #[test]
+  fn hash_finds_every_overlapping_pair() {
+    for count in [0, 1, 250, 2000] {
+      assert_eq!(hashed_pairs(&bullets(count)), all_pairs(&bullets(count)), "The broadphase lost pairs among {} bullets", count);
+    }
+  }
+
+  #[test]
+  fn large_bounds_are_found_everywhere() {
+    let floor: Aabb = Aabb { min: Vec2::new(-10000.0, 0.0), max: Vec2::new(10000.0, 10.0) };
+    let small: Aabb = Aabb { min: Vec2::new(500.0, 5.0), max: Vec2::new(510.0, 15.0) };
+    let hash: SpatialHash = SpatialHash::build([floor, small], CELL_SIZE);
+    assert_eq!(hash.query(Aabb { min: Vec2::new(9000.0, 2.0), max: Vec2::new(9001.0, 3.0) }), vec![0]);
+    assert_eq!(hash.query(small), vec![0, 1]);
+    assert_eq!(hash.pairs(), vec![(0, 1)]);
+  }
+
+  #[test]
+  fn candidates_match_brute_force_after_moves() {
+    let _serial = testing::serial();
+    let scene: NodeHandle = NodeHandle::new(Node::new());
+    let rig: NodeHandle = NodeHandle::new(RectMesh::new(Vec2::ZERO, Vec2::ONE, Color::new(0)));
+    let colliders: Vec<NodeHandle> = bullets(400).iter().map(collider).collect();
+    for (index, collider) in colliders.iter().enumerate() {
+      if index % 4 == 0 {
+        rig.add_child(index.to_string(), collider.clone());
+      }
+      Collider::register(collider.clone(), scene.id());
+    }
+    Collider::refresh_broadphase(&scene);
+
+    for collider in colliders.iter().skip(1).step_by(3) {
+      Place::field(collider, |coll: &mut Collider| &mut coll.transform).map(|transform| &mut transform.pos).with(|pos| *pos += Vec2::new(37.0, -23.0));
+    }
+    Place::field(&rig, |mesh: &mut RectMesh| &mut mesh.transform).map(|transform| &mut transform.pos).with(|pos| *pos = Vec2::new(50.0, 90.0));
+
+    let hulls: Vec<Hull> = colliders.iter().filter_map(|collider| {
+      collider.global_frame();
+      collider.cast(|coll: &mut Collider| coll.hull())
+    }).collect();
+    let slots: HashMap<u64, usize> = colliders.iter().enumerate().map(|(index, collider)| (collider.id(), index)).collect();
+    let mut found: Vec<(usize, usize)> = Vec::new();
+    for (index, hull) in hulls.iter().enumerate() {
+      for other in Collider::candidates(hull.aabb()) {
+        let other: usize = slots[&other.id()];
+        if index < other && hull.overlaps(&hulls[other]) {
+          found.push((index, other));
+        }
+      }
+    }
+    found.sort_unstable();
+    for collider in colliders.iter() {
+      Collider::unregister(collider.id());
+    }
+    assert_eq!(found, all_pairs(&hulls));
+  }
+
+  #[test]
+  #[ignore = "prints timings, run with --ignored --nocapture"]
+  fn benchmark() {
+    let _serial = testing::serial();
+    println!("{:>8} {:>12} {:>12} {:>8} {:>12} {:>8}", "bullets", "all pairs", "hash", "pairs", "collides", "hit");
+    for count in [250, 500, 1000, 2000, 4000, 8000] {
+      let hulls: Vec<Hull> = bullets(count);
+
+      let start: Instant = Instant::now();
+      all_pairs(&hulls);
+      let brute_time: f64 = millis(start);
+
+      let start: Instant = Instant::now();
+      let hashed: usize = hashed_pairs(&hulls).len();
+      let hash_time: f64 = millis(start);
+
+      let scene: NodeHandle = NodeHandle::new(Node::new());
+      let colliders: Vec<NodeHandle> = hulls.iter().map(collider).collect();
+      for collider in colliders.iter() {
+        Collider::register(collider.clone(), scene.id());
+      }
+      Collider::refresh_broadphase(&scene);
+      let start: Instant = Instant::now();
+      let hit: usize = colliders.iter().filter(|collider| Collider::collides(collider, false) == Some(true)).count();
+      let collides_time: f64 = millis(start);
+      for collider in colliders.iter() {
+        Collider::unregister(collider.id());
+      }
+
+      println!("{:>8} {:>10.2}ms {:>10.2}ms {:>8} {:>10.2}ms {:>8}", count, brute_time, hash_time, hashed, collides_time, hit);
+    }
+  }
+}
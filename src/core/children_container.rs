@@ -1,36 +1,134 @@
-use std::{collections::HashMap, hash::Hash};
+use std::hash::Hash;
 
 use crate::core::core::Downcastable;
 
+//? Children are kept in insertion order, which is also the order they are set up, updated and rendered in.
 #[derive(Debug, Clone)]
-pub struct ChildrenContainer<K, T> where K: Eq + Hash, T: Downcastable {
-  pub children: HashMap<K, T>,
+pub struct ChildrenContainer<K, T> where K: Eq + Hash {
+  pub children: Vec<(K, T)>,
 }
 
-impl<K, T> ChildrenContainer<K, T> where K: Eq + Hash, T: Downcastable {
+impl<K, T> ChildrenContainer<K, T> where K: Eq + Hash {
   pub fn new() -> Self {
-    Self { children: HashMap::new() }
+    Self { children: Vec::new() }
   }
-  pub fn add_child(&mut self, id: K, child: T) {
-    self.children.insert(id, child);
+  pub fn add_child(&mut self, id: K, child: T) -> Option<T> {
+    match self.index_of(&id) {
+      Some(index) => Some(std::mem::replace(&mut self.children[index].1, child)),
+      None => {
+        self.children.push((id, child));
+        None
+      }
+    }
   }
   pub fn clear_children(&mut self) {
     self.children.clear();
   }
   pub fn remove_child(&mut self, id: K) {
-    let _ = self.children.remove(&id);
+    let _ = self.take_child(&id);
+  }
+  pub fn take_child(&mut self, id: &K) -> Option<T> {
+    let index: usize = self.index_of(id)?;
+    Some(self.children.remove(index).1)
+  }
+  pub fn get_mut(&mut self, id: &K) -> Option<&mut T> {
+    self.children.iter_mut().find(|(key, _)| key == id).map(|(_, child)| child)
+  }
+  pub fn entries(&self) -> Vec<(K, T)> where K: Clone, T: Clone {
+    self.children.clone()
+  }
+  pub fn len(&self) -> usize {
+    self.children.len()
+  }
+  pub fn index_of(&self, id: &K) -> Option<usize> {
+    self.children.iter().position(|(key, _)| key == id)
+  }
+  pub fn child_at(&self, index: usize) -> Option<(&K, &T)> {
+    self.children.get(index).map(|(key, child)| (key, child))
+  }
+  pub fn move_child(&mut self, id: &K, index: usize) -> bool {
+    let Some(from) = self.index_of(id) else {
+      return false
+    };
+    let entry: (K, T) = self.children.remove(from);
+    let to: usize = index.min(self.children.len());
+    self.children.insert(to, entry);
+    true
+  }
+  pub fn move_to_front(&mut self, id: &K) -> bool {
+    self.move_child(id, usize::MAX)
+  }
+  pub fn move_to_back(&mut self, id: &K) -> bool {
+    self.move_child(id, 0)
   }
   pub fn foreach_child<F>(&mut self, mut func: F) where F: FnMut(&Self, &K, &mut T) {
-    let mut tmp: HashMap<K, T> = std::mem::take(&mut self.children);
+    let mut tmp: Vec<(K, T)> = std::mem::take(&mut self.children);
     tmp.iter_mut().for_each(|(id, node)| {
       func(self, id, node);
     });
     self.children = tmp;
   }
-  pub fn get_child<C>(&mut self, id: K) -> Option<&mut C> where C: 'static {
+  pub fn get_child<C>(&mut self, id: K) -> Option<&mut C> where C: 'static, T: Downcastable {
     self.children
         .iter_mut()
-        .find(|(key, _)| *key == &id)
+        .find(|(key, _)| *key == id)
         .and_then(|(_, child)| child.as_any().downcast_mut::<C>())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn container(names: &[&'static str]) -> ChildrenContainer<&'static str, u32> {
+    let mut container = ChildrenContainer::new();
+    for (value, name) in names.iter().enumerate() {
+      container.add_child(*name, value as u32);
+    }
+    container
+  }
+
+  fn order(container: &ChildrenContainer<&'static str, u32>) -> Vec<&'static str> {
+    container.children.iter().map(|(name, _)| *name).collect()
+  }
+
+  #[test]
+  fn move_child_clamps_the_index() {
+    let mut children = container(&["a", "b", "c"]);
+    assert!(children.move_child(&"a", 1));
+    assert_eq!(order(&children), ["b", "a", "c"]);
+    assert!(children.move_child(&"b", 99));
+    assert_eq!(order(&children), ["a", "c", "b"]);
+    assert!(children.move_child(&"c", 0));
+    assert_eq!(order(&children), ["c", "a", "b"]);
+  }
+
+  #[test]
+  fn move_to_front_and_back() {
+    let mut children = container(&["a", "b", "c"]);
+    assert!(children.move_to_front(&"a"));
+    assert_eq!(order(&children), ["b", "c", "a"]);
+    assert!(children.move_to_back(&"c"));
+    assert_eq!(order(&children), ["c", "b", "a"]);
+    assert_eq!(children.index_of(&"b"), Some(1));
+  }
+
+  #[test]
+  fn missing_names_are_left_alone() {
+    let mut children = container(&["a", "b"]);
+    assert_eq!(children.index_of(&"z"), None);
+    assert!(!children.move_child(&"z", 0));
+    assert!(!children.move_to_front(&"z"));
+    assert!(!children.move_to_back(&"z"));
+    assert_eq!(order(&children), ["a", "b"]);
+  }
+
+  #[test]
+  fn replacing_a_child_keeps_its_place() {
+    let mut children = container(&["a", "b", "c"]);
+    assert_eq!(children.add_child("b", 7), Some(1));
+    assert_eq!(order(&children), ["a", "b", "c"]);
+    assert_eq!(children.child_at(1), Some((&"b", &7)));
+    assert_eq!(children.index_of(&"c"), Some(2));
+  }
+}
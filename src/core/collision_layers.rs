@@ -0,0 +1,86 @@
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+use mlua::{FromLua, IntoLua, Lua, Table, Value};
+
+pub const DEFAULT_COLLISION_LAYER: &str = "default";
+pub const EVERYTHING: &str = "everything";
+
+lazy_static! {
+  static ref COLLISION_LAYERS: Arc<RwLock<Vec<String>>> = Arc::new(RwLock::new(vec![DEFAULT_COLLISION_LAYER.to_string()]));
+}
+
+//? The default layer takes the first bit unless it is declared itself.
+pub fn load_collision_layers(env: &Table) -> mlua::Result<()> {
+  let mut layers: Vec<String> = Vec::new();
+  if let Some(tbl) = env.get::<Option<Table>>("CollisionLayers")? {
+    for name in tbl.sequence_values::<String>() {
+      let name: String = name?;
+      if name == EVERYTHING || layers.contains(&name) {
+        return Err(mlua::Error::RuntimeError(format!("Collision layer '{}' cannot be declared twice or be called '{}'", name, EVERYTHING)))
+      }
+      layers.push(name);
+    }
+  }
+  if !layers.iter().any(|name| name == DEFAULT_COLLISION_LAYER) {
+    layers.insert(0, DEFAULT_COLLISION_LAYER.to_string());
+  }
+  if layers.len() > 32 {
+    return Err(mlua::Error::RuntimeError("At most 32 collision layers can be declared".to_string()))
+  }
+  *COLLISION_LAYERS.write().unwrap() = layers;
+  Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct LayerMask(pub u32);
+
+impl LayerMask {
+  pub const EVERYTHING: LayerMask = LayerMask(u32::MAX);
+
+  pub fn default_layer() -> LayerMask {
+    LayerMask::named(DEFAULT_COLLISION_LAYER).unwrap_or(LayerMask(1))
+  }
+
+  pub fn named(name: &str) -> mlua::Result<LayerMask> {
+    if name == EVERYTHING {
+      return Ok(LayerMask::EVERYTHING)
+    }
+    match COLLISION_LAYERS.read().unwrap().iter().position(|layer| layer == name) {
+      Some(bit) => Ok(LayerMask(1 << bit)),
+      None => Err(mlua::Error::RuntimeError(format!("Unknown collision layer '{}', declare it in CollisionLayers", name)))
+    }
+  }
+
+  pub fn names(self) -> Vec<String> {
+    COLLISION_LAYERS.read().unwrap().iter().enumerate()
+      .filter(|(bit, _)| self.0 & (1 << bit) != 0)
+      .map(|(_, name)| name.clone())
+      .collect()
+  }
+
+  pub fn intersects(self, other: LayerMask) -> bool {
+    self.0 & other.0 != 0
+  }
+
+  pub fn pair(layer_a: LayerMask, mask_a: LayerMask, layer_b: LayerMask, mask_b: LayerMask) -> bool {
+    mask_a.intersects(layer_b) || mask_b.intersects(layer_a)
+  }
+}
+
+impl FromLua for LayerMask {
+  fn from_lua(value: Value, lua: &Lua) -> mlua::Result<Self> {
+    match value {
+      Value::String(name) => LayerMask::named(&name.to_str()?),
+      Value::Table(tbl) => tbl.sequence_values::<String>().try_fold(LayerMask(0), |mask, name| Ok(LayerMask(mask.0 | LayerMask::named(&name?)?.0))),
+      Value::Integer(_) | Value::Number(_) => Ok(LayerMask(u32::from_lua(value, lua)?)),
+      _ => Err(mlua::Error::FromLuaConversionError { from: value.type_name(), to: "LayerMask".to_string(), message: None })
+    }
+  }
+}
+
+impl IntoLua for LayerMask {
+  fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+    self.names().into_lua(lua)
+  }
+}
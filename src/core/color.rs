@@ -1,8 +1,6 @@
-use std::error::Error;
+use mlua::{FromLua, IntoLua, Lua, MetaMethod, UserData, UserDataMethods, Value};
 
-use mlua::{Lua, Value};
-
-use crate::core::core::Luable;
+use crate::core::proxy::Place;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Color {
@@ -104,27 +102,49 @@ impl Color {
   }
 }
 
-impl Luable for Color {
-  fn as_lua(&self, lua: &Lua) -> Result<Value, Box<dyn Error>> {
-    let table = lua.create_table()?;
-
-    table.set("r", Value::Integer(self.r as i64))?;
-    table.set("g", Value::Integer(self.g as i64))?;
-    table.set("b", Value::Integer(self.b as i64))?;
-    table.set("a", Value::Integer(self.a as i64))?;
-
-    Ok(Value::Table(table))
+impl FromLua for Color {
+  fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+    match &value {
+      Value::UserData(ud) if ud.is::<Place<Color>>() => Ok(ud.borrow::<Place<Color>>()?.get()),
+      Value::Table(tbl) => Ok(Color::from_rgba(tbl.get("r")?, tbl.get("g")?, tbl.get("b")?, tbl.get("a")?)),
+      _ => Err(mlua::Error::FromLuaConversionError { from: value.type_name(), to: "Color".to_string(), message: None })
+    }
   }
+}
 
-  fn from_lua(&mut self, value: Value) -> Result<(), Box<dyn Error>> {
-    if let Some(tbl) = value.as_table() {
-      self.r = tbl.get("r")?;
-      self.g = tbl.get("g")?;
-      self.b = tbl.get("b")?;
-      self.a = tbl.get("a")?;
-      return Ok(());
-    }
+impl IntoLua for Color {
+  fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+    Place::owned(self).into_lua(lua)
+  }
+}
 
-    Err("Invalid Lua value".into())
+impl UserData for Place<Color> {
+  fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+    methods.add_meta_method(MetaMethod::Index, |lua, this, key: String| {
+      let color: Color = this.get();
+      match key.as_str() {
+        "r" => color.r.into_lua(lua),
+        "g" => color.g.into_lua(lua),
+        "b" => color.b.into_lua(lua),
+        "a" => color.a.into_lua(lua),
+        _ => Ok(Value::Nil)
+      }
+    });
+
+    methods.add_meta_method(MetaMethod::NewIndex, |_, this, (key, value): (String, u8)| {
+      match key.as_str() {
+        "r" => this.with(|color| color.r = value),
+        "g" => this.with(|color| color.g = value),
+        "b" => this.with(|color| color.b = value),
+        "a" => this.with(|color| color.a = value),
+        _ => return Err(mlua::Error::RuntimeError(format!("Color has no field '{}'", key)))
+      }
+      Ok(())
+    });
+
+    methods.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+      let color: Color = this.get();
+      Ok(format!("Color({}, {}, {}, {})", color.r, color.g, color.b, color.a))
+    });
   }
-}
\ No newline at end of file
+}
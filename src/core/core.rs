@@ -1,11 +1,8 @@
-use std::{any::Any, error::Error, f32::consts::PI, fs, path::PathBuf, sync::RwLockWriteGuard};
+use std::{any::Any, error::Error, f32::consts::PI, fs, path::PathBuf};
 
-use macroquad::{input::{KeyCode, MouseButton, is_key_down, is_key_pressed, is_key_released, is_mouse_button_down, is_mouse_button_pressed, is_mouse_button_released, mouse_position}, miniquad::window, texture::{DrawTextureParams, Image, Texture2D, load_texture}, window::{Conf, screen_height, screen_width}};
-use mlua::{AnyUserData, Chunk, Function, Lua, MultiValue, Table, UserData, Value};
-use crate::core::{color::Color, engine::MAIN_CAMERA, image::Img, keys::Stringable, nodelike::NodeLike, nodes::{button::{SpriteButton, TextButton}, camera::Camera, clickable_area::ClickableArea, collider::Collider, node::Node, rectmesh::RectMesh, soundplayer::SoundPlayer, sprite::Sprite, text::Text}, script_manager::{ScriptManager, ScriptManagerSecret}, transform::Transform, vec2::Vec2};
-
-pub struct LuaTexture(pub Texture2D);
-impl UserData for LuaTexture {}
+use macroquad::{input::{KeyCode, MouseButton, is_key_down, is_key_pressed, is_key_released, is_mouse_button_down, is_mouse_button_pressed, is_mouse_button_released}, miniquad::window, window::Conf};
+use mlua::{Chunk, Function, Lua, MultiValue, Table, Value};
+use crate::core::{collision_layers::LayerMask, color::Color, engine::{MAIN_CAMERA, is_headless, refresh_camera, render_alpha, request_quit, screen_to_world, world_to_screen}, image::Img, ivec2::IVec2, keys::Stringable, nodes::{button::{SpriteButton, TextButton}, camera::Camera, canvas_layer::CanvasLayer, character_body::CharacterBody, clickable_area::ClickableArea, collider::Collider, joint::{Joint, JointKind}, node::Node, rectmesh::RectMesh, rigidbody::{BodyMode, RigidBody}, soundplayer::SoundPlayer, sprite::Sprite, text::Text, viewport::Viewport}, proxy::NodeHandle, query, scene::{SceneRequest, request_scene}, script_manager::ScriptManager, shape::Shape, stretch::{virtual_mouse, virtual_size}, transform::Transform, vec2::Vec2};
 
 #[derive(Debug)]
 pub struct WindowConfig {
@@ -24,20 +21,7 @@ impl WindowConfig {
     Ok(
       WindowConfig { 
         title: lua.globals().get("Title").unwrap_or("Default Window".to_string()), 
-        size: {
-          let tmp: Result<Value, mlua::Error> = lua.globals().get("Size");
-          if tmp.is_err() {
-            Vec2::new(500, 500)
-          } else {
-            let mut vec: Vec2 = Vec2::ZERO.clone();
-            let r: Result<(), Box<dyn Error>> = vec.from_lua(tmp.unwrap());
-            if r.is_err() {
-              Vec2::new(500, 500)
-            } else {
-              vec
-            }
-          }
-        }, 
+        size: lua.globals().get::<Vec2>("Size").unwrap_or(Vec2::new(500.0, 500.0)), 
         fullscreen: lua.globals().get("Fullscreen").unwrap_or(false), 
         resizable: lua.globals().get("Resizable").unwrap_or(true), 
       }
@@ -49,8 +33,8 @@ impl Into<Conf> for WindowConfig {
   fn into(self) -> Conf {
     Conf { 
       window_title: self.title, 
-      window_width: self.size.get_x(), 
-      window_height: self.size.get_y(),  
+      window_width: self.size.get_x() as i32, 
+      window_height: self.size.get_y() as i32,  
       fullscreen: self.fullscreen,  
       window_resizable: self.resizable, 
       ..Default::default()
@@ -62,83 +46,19 @@ pub fn radians(degrees: f32) -> f32 {
   degrees * PI / 180.0
 }
 
-pub trait Luable {
-  fn as_lua(&self, lua: &Lua) -> Result<Value, Box<dyn Error>>;
-  fn from_lua(&mut self, value: Value) -> Result<(), Box<dyn Error>>;
-}
-
 pub trait Downcastable {
   fn as_any(&mut self) -> &mut dyn Any;
 }
 
-pub fn call_constructor(kind: &str, node: Value) -> Result<Box<dyn NodeLike>, mlua::Error> {
-  Ok(
-    match kind {
-    "Node" => {
-      let mut tmp: Node = Node::new();
-      tmp.from_lua(node).expect("Invalid Lua Value");
-      Box::new(tmp)
-    },
-    "RectMesh" => {
-      let mut tmp: RectMesh = RectMesh::new(Vec2::ZERO, Vec2::ZERO, Color::new(0));
-      tmp.from_lua(node).expect("Invalid Lua Value");
-      Box::new(tmp)
-    },
-    "ClickableArea" => {
-      let mut tmp: ClickableArea = ClickableArea::new(Vec2::ZERO, Vec2::ZERO);
-      tmp.from_lua(node).expect("Invalid Lua Value");
-      Box::new(tmp)
-    },
-    "Sprite" => {
-      let mut tmp: Sprite = Sprite::new(Vec2::ZERO, Vec2::ZERO, Img::new(""));
-      tmp.from_lua(node).expect("Invalid Lua Value");
-      Box::new(tmp)
-    },
-    "Text" => {
-      let mut tmp: Text = Text::new("", Vec2::ZERO, 0, Color::new(0));
-      tmp.from_lua(node).expect("Invalid Lua Value");
-      Box::new(tmp)
-    },
-    "Camera" => {
-      let mut tmp: Camera = Camera::new(Vec2::ZERO, Vec2::ZERO, 0.0);
-      tmp.from_lua(node).expect("Invalid Lua Value");
-      Box::new(tmp)
-    },
-    "SoundPlayer" => {
-      let mut tmp: SoundPlayer = SoundPlayer::empty();
-      tmp.from_lua(node).expect("Invalid Lua Value");
-      Box::new(tmp)
-    },
-    "Collider" => {
-      let mut tmp: Collider = Collider::empty();
-      tmp.from_lua(node).expect("Invalid Lua Value");
-      Box::new(tmp)
-    },
-    "TextButton" => {
-      let mut tmp: TextButton = TextButton::new("", Vec2::ZERO, 0, Color::new(0));
-      tmp.from_lua(node).expect("Invalid Lua Value");
-      Box::new(tmp)
-    },
-    "SpriteButton" => {
-      let mut tmp: Sprite = Sprite::new(Vec2::ZERO, Vec2::ZERO, Img::new(""));
-      tmp.from_lua(node).expect("Invalid Lua Value");
-      Box::new(tmp)
-    },
-    _ => {
-      return Err(mlua::Error::RuntimeError("Node not recognized".into()))
-    }
-  }
-  )
-}
-
-pub fn load_persistrent(lua: &Lua, env: &Table) -> Result<(), Box<dyn Error>> {
-  env.set("window_width", screen_width())?;
-  env.set("window_height", screen_height())?;
-  let tmp: Vec2 = {
-    let (mx, my) = mouse_position();
-    Vec2::new(mx as i32, my as i32)
-  };
-  env.set("mouse_pos", tmp.as_lua(lua)?)?;
+pub fn load_persistrent(_lua: &Lua, env: &Table) -> Result<(), Box<dyn Error>> {
+  env.set("headless", is_headless())?;
+  env.set("interpolation_alpha", render_alpha())?;
+  let size: Vec2 = virtual_size();
+  env.set("window_width", size.get_x())?;
+  env.set("window_height", size.get_y())?;
+  let tmp: Vec2 = if is_headless() { Vec2::ZERO } else { virtual_mouse() };
+  env.set("mouse_pos", tmp)?;
+  env.set("mouse_world_pos", screen_to_world(tmp))?;
   Ok(())
 }
 
@@ -167,6 +87,9 @@ pub fn init_env_commons(lua: &Lua, env: &Table) -> Result<(), Box<dyn Error>> {
   })?)?;
 
   env.set("keydown", lua.create_function(|_, key: String| {
+    if is_headless() {
+      return Ok(false)
+    }
     let code: Option<Box<KeyCode>> = KeyCode::from_string(&key);
     if let Some(keycode) = code {
       return Ok(is_key_down(*keycode))
@@ -175,6 +98,9 @@ pub fn init_env_commons(lua: &Lua, env: &Table) -> Result<(), Box<dyn Error>> {
   })?)?;
 
   env.set("keypressed", lua.create_function(|_, key: String| {
+    if is_headless() {
+      return Ok(false)
+    }
     let code: Option<Box<KeyCode>> = KeyCode::from_string(&key);
     if let Some(keycode) = code {
       return Ok(is_key_pressed(*keycode))
@@ -183,6 +109,9 @@ pub fn init_env_commons(lua: &Lua, env: &Table) -> Result<(), Box<dyn Error>> {
   })?)?;
 
   env.set("keyreleased", lua.create_function(|_, key: String| {
+    if is_headless() {
+      return Ok(false)
+    }
     let code: Option<Box<KeyCode>> = KeyCode::from_string(&key);
     if let Some(keycode) = code {
       return Ok(is_key_released(*keycode))
@@ -191,6 +120,9 @@ pub fn init_env_commons(lua: &Lua, env: &Table) -> Result<(), Box<dyn Error>> {
   })?)?;
 
   env.set("mkeydown", lua.create_function(|_, key: i64| {
+    if is_headless() {
+      return Ok(false)
+    }
     let button = match key {
       0 => MouseButton::Left,
       1 => MouseButton::Right,
@@ -203,6 +135,9 @@ pub fn init_env_commons(lua: &Lua, env: &Table) -> Result<(), Box<dyn Error>> {
   })?)?;
 
   env.set("mkeypressed", lua.create_function(|_, key: i64| {
+    if is_headless() {
+      return Ok(false)
+    }
     let button = match key {
       0 => MouseButton::Left,
       1 => MouseButton::Right,
@@ -215,6 +150,9 @@ pub fn init_env_commons(lua: &Lua, env: &Table) -> Result<(), Box<dyn Error>> {
   })?)?;
 
   env.set("mkeyreleased", lua.create_function(|_, key: i64| {
+    if is_headless() {
+      return Ok(false)
+    }
     let button = match key {
       0 => MouseButton::Left,
       1 => MouseButton::Right,
@@ -226,190 +164,204 @@ pub fn init_env_commons(lua: &Lua, env: &Table) -> Result<(), Box<dyn Error>> {
     Ok(is_mouse_button_released(button))
   })?)?;
 
-  env.set("Vec2", lua.create_function(|this, (x, y) : (i32, i32)| {
-    Ok(Vec2::new(x, y).as_lua(this).expect("Cannot convert to Vec2"))
+  env.set("Vec2", lua.create_function(|_, (x, y) : (f32, f32)| {
+    Ok(Vec2::new(x, y))
   })?)?;
 
-  env.set("Transform", lua.create_function(|this, (pos, size) : (Table, Table)| {
-    let mut position = Vec2::ZERO.clone();
-    let mut sz = Vec2::ZERO.clone();
-    position.from_lua(Value::Table(pos)).expect("Cannot convert to Vec2");
-    sz.from_lua(Value::Table(size)).expect("Cannot convert to Vec2");
-    Ok(Transform::new(position, sz).as_lua(this).expect("Cannot convert to Transform"))
+  env.set("IVec2", lua.create_function(|_, (x, y) : (i32, i32)| {
+    Ok(IVec2::new(x, y))
   })?)?;
 
-  env.set("Img", lua.create_function(|this, (tex, rot, src, tint, fx, fy) : (String, f32, Value, Value, bool, bool)| {
+  env.set("screen_to_world", lua.create_function(|_, point: Vec2| {
+    Ok(screen_to_world(point))
+  })?)?;
+
+  env.set("world_to_screen", lua.create_function(|_, point: Vec2| {
+    Ok(world_to_screen(point))
+  })?)?;
+
+  env.set("Transform", lua.create_function(|_, (pos, size) : (Vec2, Vec2)| {
+    Ok(Transform::new(pos, size))
+  })?)?;
+
+  env.set("Img", lua.create_function(|_, (tex, rot, src, tint, fx, fy) : (String, f32, Option<Vec2>, Option<Color>, bool, bool)| {
     let mut im = Img::new(&tex)
     .with_degrees(rot)
     .flip(fx, fy);
     
-    if src.is_table() {
-      let mut v = Vec2::ZERO.clone();
-      v.from_lua(src).expect("Cannot convert to Vec2");
+    if let Some(v) = src {
       im = im.section(v);
     }
 
-    if tint.is_table() {
-      let mut col = Color::new(0);
-      col.from_lua(tint).expect("Cannot convert to Color");
+    if let Some(col) = tint {
       im = im.tint(col);
     }
 
-    Ok(im.as_lua(this).expect("Cannot convert to Color"))
+    Ok(im)
   })?)?;
 
-  env.set("ColorRgba", lua.create_function(|this, (r, g, b, a) : (u8, u8, u8, u8)| {
-    let col = Color::from_rgba(r, g, b, a);
-    Ok(col.as_lua(this).expect("Cannot convert to Color"))
+  env.set("ColorRgba", lua.create_function(|_, (r, g, b, a) : (u8, u8, u8, u8)| {
+    Ok(Color::from_rgba(r, g, b, a))
   })?)?;
 
-  env.set("ColorRgb", lua.create_function(|this, (r, g, b) : (u8, u8, u8)| {
-    let col = Color::from_rgb(r, g, b);
-    Ok(col.as_lua(this).expect("Cannot convert to Color"))
+  env.set("ColorRgb", lua.create_function(|_, (r, g, b) : (u8, u8, u8)| {
+    Ok(Color::from_rgb(r, g, b))
   })?)?;
 
-  env.set("Color", lua.create_function(|this, i: u32| {
-    let col = Color::new(i);
-    Ok(col.as_lua(this).expect("Cannot convert to Color"))
+  env.set("Color", lua.create_function(|_, i: u32| {
+    Ok(Color::new(i))
   })?)?;
 
-  env.set("ColorHex", lua.create_function(|this, s: String| {
-    let col = Color::from_hex(&s);
-    Ok(col.as_lua(this).expect("Cannot convert to Color"))
+  env.set("ColorHex", lua.create_function(|_, s: String| {
+    Ok(Color::from_hex(&s))
   })?)?;
 
-  env.set("Node", lua.create_function(|this, ()| {
-    Ok(Node::new().as_lua(this).expect("Cannot convert Node to Lua Value"))
+  env.set("Node", lua.create_function(|_, ()| {
+    Ok(NodeHandle::new(Node::new()))
   })?)?;
 
-  env.set("RectMesh", lua.create_function(|this, (pos, sz, col) : (Table, Table, Table)| {
-    let mut position: Vec2 = Vec2::ZERO.clone();
-    let mut size: Vec2 = Vec2::ZERO.clone();
-    let mut color: Color = Color::new(0);
-    position.from_lua(Value::Table(pos)).expect("Invalid Lua Value");
-    size.from_lua(Value::Table(sz)).expect("Invalid Lua Value");
-    color.from_lua(Value::Table(col)).expect("Invalid Lua Value");
-    Ok(RectMesh::new(position, size, color).as_lua(this).expect("Cannot convert RectMesh to Lua Value"))
+  env.set("RectMesh", lua.create_function(|_, (pos, sz, col) : (Vec2, Vec2, Color)| {
+    Ok(NodeHandle::new(RectMesh::new(pos, sz, col)))
   })?)?;
 
-  env.set("ClickableArea", lua.create_function(|this, (pos, sz) : (Table, Table)| {
-    let mut position: Vec2 = Vec2::ZERO.clone();
-    let mut size: Vec2 = Vec2::ZERO.clone();
-    position.from_lua(Value::Table(pos)).expect("Invalid Lua Value");
-    size.from_lua(Value::Table(sz)).expect("Invalid Lua Value");
-    Ok(ClickableArea::new(position, size).as_lua(this).expect("Cannot convert ClickableArea to Lua Value"))
+  env.set("ClickableArea", lua.create_function(|_, (pos, sz) : (Vec2, Vec2)| {
+    Ok(NodeHandle::new(ClickableArea::new(pos, sz)))
   })?)?;
 
-  env.set("Sprite", lua.create_function(|this, (pos, sz, img) : (Table, Table, Table)| {
-    let mut position: Vec2 = Vec2::ZERO.clone();
-    let mut size: Vec2 = Vec2::ZERO.clone();
-    let mut im: Img = Img::new("");
-    position.from_lua(Value::Table(pos)).expect("Invalid Lua Value");
-    size.from_lua(Value::Table(sz)).expect("Invalid Lua Value");
-    im.from_lua(Value::Table(img)).expect("Invalid Lua Value");
-    Ok(Sprite::new(position, size, im).as_lua(this).expect("Cannot convert Sprite to Lua Value"))
+  env.set("Sprite", lua.create_function(|_, (pos, sz, img) : (Vec2, Vec2, Img)| {
+    Ok(NodeHandle::new(Sprite::new(pos, sz, img)))
   })?)?;
 
-  env.set("Text", lua.create_function(|this, (text, pos, size, col): (String, Table, u16, Table)| {
-    let mut position: Vec2 = Vec2::ZERO.clone();
-    position.from_lua(Value::Table(pos)).expect("Invalid Lua Value");
+  env.set("Text", lua.create_function(|_, (text, pos, size, col): (String, Vec2, u16, Color)| {
+    Ok(NodeHandle::new(Text::new(&text, pos, size, col)))
+  })?)?;
 
-    let mut color: Color = Color::new(0);
-    color.from_lua(Value::Table(col)).expect("Invalid Lua Value");
+  env.set("Camera", lua.create_function(|_, (pos, surface, focal_length): (Vec2, Vec2, Option<f32>)| {
+    Ok(NodeHandle::new(Camera::new(pos, surface, focal_length.map_or(1.0, Camera::zoom_for))))
+  })?)?;
 
-    Ok(Text::new(&text, position, size, color).as_lua(this).expect("Cannot convert Text to Lua Value"))
+  env.set("SoundPlayer", lua.create_function(|_, sound: String| {
+    Ok(NodeHandle::new(SoundPlayer::new(&sound)))
   })?)?;
 
-  env.set("Camera", lua.create_function(|this, (pos, surface, focal_length): (Table, Table, f32)| {
-    let mut position: Vec2 = Vec2::ZERO.clone();
-    position.from_lua(Value::Table(pos)).expect("Invalid Lua Value");
-    let mut size: Vec2 = Vec2::ZERO.clone();
-    size.from_lua(Value::Table(surface)).expect("Invalid Lua Value");
-    Ok(Camera::new(position, size, focal_length).as_lua(this).expect("Cannot convert Camera to Lua Value"))
+  env.set("Viewport", lua.create_function(|_, (pos, size, camera): (Vec2, Vec2, Option<NodeHandle>)| {
+    if let Some(cam) = &camera && cam.cast(|_: &mut Camera| ()).is_none() {
+      return Err(mlua::Error::RuntimeError("Viewport camera must be a Camera".to_string()))
+    }
+    Ok(NodeHandle::new(Viewport::new(pos, size, camera)))
   })?)?;
 
-  env.set("SoundPlayer", lua.create_function(|this, sound: String| {
-    Ok(SoundPlayer::new(&sound).as_lua(this).expect("Cannot convert SoundPlayer to Lua Value"))
+  env.set("CanvasLayer", lua.create_function(|_, order: Option<i32>| {
+    Ok(NodeHandle::new(CanvasLayer::new(order.unwrap_or(0))))
   })?)?;
 
-  env.set("Collider", lua.create_function(|this, (pos, size, layer): (Table, Table, Option<String>)| {
-    let mut position = Vec2::ZERO.clone();
-    position.from_lua(Value::Table(pos)).expect("Cannot convert Lua Value to Vec2");
-    let mut sz = Vec2::ZERO.clone();
-    sz.from_lua(Value::Table(size)).expect("Cannot convert Lua Value to Vec2");
-    let collider = Collider::new(position, sz, layer.unwrap_or("everything".to_string()));
-    let val = collider.as_lua(this).expect("Cannot convert Collider to Lua Value");
-    Ok(val)
+  //? Given only a layer, a collider collides with that layer alone, like colliders always did with their single layer.
+  env.set("Collider", lua.create_function(|_, (pos, size, layer, shape, mask): (Vec2, Vec2, Option<LayerMask>, Option<Shape>, Option<LayerMask>)| {
+    let mask: LayerMask = mask.or(layer).unwrap_or(LayerMask::EVERYTHING);
+    let mut collider: Collider = Collider::new(pos, size, layer.unwrap_or_else(LayerMask::default_layer), mask);
+    if let Some(shape) = shape {
+      collider.shape = shape;
+    }
+    Ok(NodeHandle::new(collider))
   })?)?;
 
-  env.set("TextButton", lua.create_function(|this, (text, pos, size, col): (String, Table, u16, Table)| {
-    let mut position: Vec2 = Vec2::ZERO.clone();
-    position.from_lua(Value::Table(pos)).expect("Invalid Lua Value");
-    let mut color: Color = Color::new(0);
-    color.from_lua(Value::Table(col)).expect("Invalid Lua Value");
-    let btn = TextButton::new(&text, position, size, color);
-    Ok(btn.as_lua(this).expect("Cannot convert TextButton to Lua Value"))
+  env.set("RigidBody", lua.create_function(|_, (pos, mode): (Vec2, Option<String>)| {
+    let mode: BodyMode = BodyMode::parse(mode.as_deref().unwrap_or("dynamic"))?;
+    Ok(NodeHandle::new(RigidBody::new(pos, mode)))
   })?)?;
 
-  env.set("SpriteButton", lua.create_function(|this, (pos, sz, img) : (Table, Table, Table)| {
-    let mut position: Vec2 = Vec2::ZERO.clone();
-    let mut size: Vec2 = Vec2::ZERO.clone();
-    let mut im: Img = Img::new("");
-    position.from_lua(Value::Table(pos)).expect("Invalid Lua Value");
-    size.from_lua(Value::Table(sz)).expect("Invalid Lua Value");
-    im.from_lua(Value::Table(img)).expect("Invalid Lua Value");
-    let btn = SpriteButton::new(position, size, im);
-    Ok(btn.as_lua(this).expect("Cannot convert SpriteButton to Lua Value"))
+  env.set("CharacterBody", lua.create_function(|_, pos: Vec2| {
+    Ok(NodeHandle::new(CharacterBody::new(pos)))
   })?)?;
 
-  env.set("embed", lua.create_function_mut(|this, (script, node): (String, Table)| {
-    let scripts: AnyUserData = match node.get::<Table>("base") {
-      Ok(tbl) => {
-        tbl.get::<AnyUserData>("scripts")?
-      },
-      Err(e) => match node.get::<AnyUserData>("scripts") {
-        Ok(tbl) => tbl,
-        Err(err) => {
-          return Err(mlua::Error::RuntimeError(format!("Seriously, dude. How did you even crash this?\n {}\n\n{}", e, err).into()))
-        }
+  env.set("Joint", lua.create_function(|_, (kind, body_a, body_b, anchor_a, anchor_b): (String, NodeHandle, Option<NodeHandle>, Option<Vec2>, Option<Vec2>)| {
+    let mut joint: Joint = Joint::new(JointKind::parse(&kind)?, &body_a, body_b.as_ref());
+    joint.anchor_a = anchor_a.unwrap_or(Vec2::ZERO);
+    joint.anchor_b = match (anchor_b, &body_b) {
+      (Some(anchor), _) => anchor,
+      (None, Some(_)) => Vec2::ZERO,
+      (None, None) => {
+        body_a.global_frame();
+        body_a.with(|body| body.get_transform().map(|transform| transform.child_frame().to_global(joint.anchor_a))).unwrap_or(joint.anchor_a)
       }
     };
+    Ok(NodeHandle::new(joint))
+  })?)?;
 
-    let scripts: Table = ScriptManagerSecret::from_userdata(scripts).expect("Cannot get scripts");
-    
-    let later: Table = node.clone();
-    let environment: Table = ScriptManager::create_environment(this, Value::Table(node)).expect("Cannot create environment");
-        
-    this.load(fs::read_to_string(&script)?)
-    .set_environment(environment.clone())
-    .set_name(script)
-    .exec()?;
-    scripts.push(environment)?;
-    
-    Ok(later)
+  env.set("raycast", lua.create_function(|_, (from, to, mask): (Vec2, Vec2, Option<LayerMask>)| {
+    Ok(query::raycast(from, to, mask.unwrap_or(LayerMask::EVERYTHING)))
+  })?)?;
+
+  env.set("query_point", lua.create_function(|_, (point, mask): (Vec2, Option<LayerMask>)| {
+    Ok(query::query_point(point, mask.unwrap_or(LayerMask::EVERYTHING)))
+  })?)?;
+
+  env.set("query_rect", lua.create_function(|_, (pos, size, mask): (Vec2, Vec2, Option<LayerMask>)| {
+    Ok(query::query_rect(pos, size, mask.unwrap_or(LayerMask::EVERYTHING)))
+  })?)?;
+
+  env.set("query_circle", lua.create_function(|_, (center, radius, mask): (Vec2, f32, Option<LayerMask>)| {
+    Ok(query::query_circle(center, radius, mask.unwrap_or(LayerMask::EVERYTHING)))
+  })?)?;
+
+  env.set("sweep_rect", lua.create_function(|_, (pos, size, motion, mask): (Vec2, Vec2, Vec2, Option<LayerMask>)| {
+    Ok(query::sweep_rect(pos, size, motion, mask.unwrap_or(LayerMask::EVERYTHING)))
+  })?)?;
+
+  env.set("sweep_circle", lua.create_function(|_, (center, radius, motion, mask): (Vec2, f32, Vec2, Option<LayerMask>)| {
+    Ok(query::sweep_circle(center, radius, motion, mask.unwrap_or(LayerMask::EVERYTHING)))
+  })?)?;
+
+  env.set("TextButton", lua.create_function(|_, (text, pos, size, col): (String, Vec2, u16, Color)| {
+    Ok(NodeHandle::new(TextButton::new(&text, pos, size, col)))
+  })?)?;
+
+  env.set("SpriteButton", lua.create_function(|_, (pos, sz, img) : (Vec2, Vec2, Img)| {
+    Ok(NodeHandle::new(SpriteButton::new(pos, sz, img)))
+  })?)?;
+
+  env.set("embed", lua.create_function_mut(|this, (script, node): (String, NodeHandle)| {
+    ScriptManager::addScript(PathBuf::from(script), this, node.clone()).map_err(|err| mlua::Error::RuntimeError(err.to_string()))?;
+    Ok(node)
   })?)?;
 
   env.set("quit", lua.create_function(|_, ()| {
+    if is_headless() {
+      request_quit();
+      return Ok(())
+    }
     window::request_quit();
     Ok(())
   })?)?;
 
-  env.set("use_camera", lua.create_function(|_, camera: Table| {
-    let mut cam = Camera::new(Vec2::ZERO, Vec2::ZERO, 0.0);
-    cam.from_lua(Value::Table(camera)).expect("Invalid given camera");
-    MAIN_CAMERA.write().unwrap().replace(cam);
+  env.set("change_scene", lua.create_function(|_, path: String| {
+    request_scene(SceneRequest::Change(path));
     Ok(())
   })?)?;
 
-  env.set("with_camera", lua.create_function(|this, func: Function| {
-    let mut cam: Camera = {
-      let mut guard = MAIN_CAMERA.write().unwrap();
-      guard.take().ok_or_else(|| {mlua::Error::RuntimeError("Main Camera is not set. No camera to work on.".to_string())})?
-    };
-    let value: Value = cam.as_lua(this).map_err(|_| mlua::Error::RuntimeError("Cannot convert Camera to Lua".into()))?;
-    let returned: Table = func.call::<Table>(value)?;
-    cam.from_lua(Value::Table(returned)).map_err(|_| mlua::Error::RuntimeError("Cannot convert Lua back to Camera".into()))?;
-    MAIN_CAMERA.write().unwrap().replace(cam);
+  env.set("push_scene", lua.create_function(|_, path: String| {
+    request_scene(SceneRequest::Push(path));
+    Ok(())
+  })?)?;
+
+  env.set("pop_scene", lua.create_function(|_, ()| {
+    request_scene(SceneRequest::Pop);
+    Ok(())
+  })?)?;
+
+  env.set("use_camera", lua.create_function(|_, camera: NodeHandle| {
+    if camera.cast(|_: &mut Camera| ()).is_none() {
+      return Err(mlua::Error::RuntimeError("Invalid given camera".to_string()))
+    }
+    MAIN_CAMERA.write().unwrap().replace(camera);
+    refresh_camera();
+    Ok(())
+  })?)?;
+
+  env.set("with_camera", lua.create_function(|_, func: Function| {
+    let cam: NodeHandle = MAIN_CAMERA.read().unwrap().clone().ok_or_else(|| {mlua::Error::RuntimeError("Main Camera is not set. No camera to work on.".to_string())})?;
+    func.call::<()>(cam)?;
+    refresh_camera();
     Ok(())
   })?)?;
 
@@ -1,152 +1,375 @@
 
-use std::{error::Error, fs, path::PathBuf, process::Child, str::FromStr, sync::{Arc, RwLock, RwLockReadGuard}};
+use std::{error::Error, sync::{Arc, RwLock, atomic::{AtomicBool, Ordering}}};
 
 use lazy_static::lazy_static;
-use macroquad::{input::{KeyCode, MouseButton, is_key_down, is_key_pressed, is_key_released, is_mouse_button_down, is_mouse_button_pressed, is_mouse_button_released}, prelude::warn, time::get_frame_time, window::{clear_background, next_frame}};
-use mlua::{Chunk, ExternalError, Function, Lua, MultiValue, Table, Value};
+use macroquad::{prelude::warn, time::get_frame_time, window::next_frame};
+use mlua::{Lua, Table};
 
-use crate::core::{children_container::ChildrenContainer, color::Color, core::{Downcastable, Luable, call_constructor, init_env_commons, load_persistrent}, image::Img, keys::Stringable, nodelike::NodeLike, nodes::{camera::Camera, clickable_area::ClickableArea, node::Node, rectmesh::RectMesh, sprite::Sprite}, script_manager::ScriptManager, vec2::Vec2};
+use crate::core::{collision_layers::load_collision_layers, color::Color, core::WindowConfig, layering::load_render_layers, nodes::camera::{Camera, CameraView}, physics::load_physics, proxy::NodeHandle, scene::{Scene, SceneRequest, take_scene_requests}, stretch::{begin_frame, end_frame, load_stretch}, vec2::Vec2};
 
 lazy_static! {
-  pub static ref MAIN_CAMERA: Arc<RwLock<Option<Camera>>> = Arc::new(RwLock::new(None));
+  pub static ref MAIN_CAMERA: Arc<RwLock<Option<NodeHandle>>> = Arc::new(RwLock::new(None));
+  static ref CAMERA_VIEW: Arc<RwLock<Option<CameraView>>> = Arc::new(RwLock::new(None));
+  pub static ref HEADLESS_WINDOW: Arc<RwLock<Option<Vec2>>> = Arc::new(RwLock::new(None));
+  pub static ref RENDER_ALPHA: Arc<RwLock<f32>> = Arc::new(RwLock::new(1.0));
 }
 
-pub fn main_camera<'a>() -> RwLockReadGuard<'a, Option<Camera>> {
-  MAIN_CAMERA.read().unwrap()
+const MAX_FIXED_STEPS: u32 = 8;
+
+static QUIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+pub fn main_camera() -> Option<CameraView> {
+  CAMERA_VIEW.read().unwrap().clone()
+}
+
+//? Nodes render while their own lock is held, so they read a copy of the camera taken here instead of the camera node.
+pub fn refresh_camera() {
+  let camera: Option<NodeHandle> = MAIN_CAMERA.read().unwrap().clone();
+  *CAMERA_VIEW.write().unwrap() = camera.and_then(|cam| cam.cast(|cam: &mut Camera| cam.view()));
+}
+
+pub fn use_render_camera(view: Option<CameraView>) {
+  *CAMERA_VIEW.write().unwrap() = view;
+}
+
+pub fn screen_to_world(point: Vec2) -> Vec2 {
+  match main_camera() {
+    Some(cam) => cam.to_world(point),
+    None => point
+  }
+}
+
+pub fn world_to_screen(point: Vec2) -> Vec2 {
+  match main_camera() {
+    Some(cam) => cam.to_screen(point),
+    None => point
+  }
+}
+
+//? The target's position is read before the camera is locked, since the two may share ancestors.
+pub fn update_camera(camera: &NodeHandle, deltatime: f32) {
+  let target: Option<Vec2> = camera.cast(|cam: &mut Camera| cam.target()).flatten().map(|target| target.global_frame().pos);
+  camera.cast(|cam: &mut Camera| {
+    if let Some(target) = target {
+      cam.follow(target, deltatime);
+    }
+    cam.decay_trauma(deltatime);
+  });
+}
+
+//? Without a window there is no macroquad context: input, textures, fonts and sounds must not be touched.
+pub fn is_headless() -> bool {
+  HEADLESS_WINDOW.read().unwrap().is_some()
+}
+
+pub fn headless_window() -> Option<Vec2> {
+  *HEADLESS_WINDOW.read().unwrap()
+}
+
+pub fn render_alpha() -> f32 {
+  *RENDER_ALPHA.read().unwrap()
+}
+
+pub fn request_quit() {
+  QUIT_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+pub fn quit_requested() -> bool {
+  QUIT_REQUESTED.load(Ordering::Relaxed)
 }
 
 pub struct Engine {
   pub bg_color: Color,
-  pub children: ChildrenContainer<String, Box<dyn NodeLike + Send + Sync>>,
-  
-  environment: Table,
+  scenes: Vec<Scene>,
 
   lua: Lua,
 
+  fixed_dt: Option<f32>,
+  accumulator: f32,
 }
 
 impl Engine {
-  pub fn new(bg: Color, env: Table) -> Self {
-    Engine {
-      bg_color: bg,
-      children: ChildrenContainer::new(),
-      lua: Lua::new(),
-      environment: env
-    }
+  pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+    Engine::open(path, None)
   }
 
-  fn load_children(&mut self) {
-    let new_children: Table = self.environment.get("root").expect("Cannot update properties of 'root'");
-      self.children.clear_children();
-      new_children.for_each(|name: String, node: Table| {
-        let kind: String = node.get::<Function>("kind")?.call::<String>(())?;
-        let gotten_node : Box<dyn NodeLike + Send + Sync> = call_constructor(&kind, Value::Table(node))?;
-        self.children.add_child(name, gotten_node);
-        Ok(())
-      }).expect("Cannot iterate root children");
+  pub fn load_headless(path: &str) -> Result<Self, Box<dyn Error>> {
+    let config: WindowConfig = WindowConfig::load(path)?;
+    Engine::open(path, Some(config.size))
   }
 
-  fn init_env(lua: &Lua, env: &Table) -> Result<(), Box<dyn Error>> {
-    env.set("root", Value::Table(lua.create_table()?))?;
-    
-    init_env_commons(lua, env)?;
+  //? Globals left over from an engine that ran earlier in this process would stop or misplace this one.
+  fn open(path: &str, headless: Option<Vec2>) -> Result<Self, Box<dyn Error>> {
+    *HEADLESS_WINDOW.write().unwrap() = headless;
+    QUIT_REQUESTED.store(false, Ordering::Relaxed);
+    MAIN_CAMERA.write().unwrap().take();
+    *RENDER_ALPHA.write().unwrap() = 1.0;
+    take_scene_requests();
 
-    let environment = env.clone();
-    env.set("add_node", lua.create_function_mut(move |this, (name, node): (String, Table)| {
-      let later = node.clone();
-      
-      let root: Table = environment.get("root")?;
-      root.set(name, node)?;
+    let lua: Lua = Lua::new();
+    let scene: Scene = Scene::load(&lua, path)?;
+    let color: Color = scene.bg_color.unwrap_or(Color::new(0));
+    let mut engine: Engine = Engine { bg_color: color, scenes: vec![scene], lua, fixed_dt: None, accumulator: 0.0 };
+    engine.load_settings()?;
+    Ok(engine)
+  }
 
-      Ok(later)
-    })?)?;
+  //? Layers, stretch and physics are globals, so they follow whichever scene is on top.
+  fn load_settings(&mut self) -> Result<(), Box<dyn Error>> {
+    let env: &Table = self.scenes.last().expect("No scene loaded").environment();
+    load_render_layers(env)?;
+    load_stretch(env)?;
+    load_physics(env)?;
+    load_collision_layers(env)?;
 
+    self.fixed_dt = match env.get::<Option<f32>>("PhysicsRate")? {
+      Some(rate) if rate > 0.0 => Some(1.0 / rate),
+      _ => None
+    };
+    self.accumulator = 0.0;
     Ok(())
-  } 
+  }
 
-  pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
-    let lua: Lua = Lua::new();
-    let file_content: String = fs::read_to_string(path)?;
-    let chunk: Chunk = lua.load(file_content);
-    let environment: Table = lua.create_table()?;
-    Engine::init_env(&lua, &environment)?;
-    chunk.set_environment(environment.clone()).exec()?;
-
-    let mut color: Color = Color::new(0);
-    let tmp = environment.get::<Table>("color");
-    if let Ok(tbl) = tmp {
-      color.from_lua(Value::Table(tbl)).ok();
+  fn reload_settings(&mut self) {
+    if let Err(err) = self.load_settings() {
+      warn!("Cannot load the settings of {}: {}", self.scenes.last().expect("No scene loaded").path, err);
     }
-    Ok( Engine { bg_color: color, children: ChildrenContainer::new(), environment, lua } )
   }
 
-  pub fn add_script_to_node<N>(&self, node: &mut N, path: &str) where N: NodeLike + Luable {
-    let this: Value = node.as_lua(&self.lua).expect("Cannot get lua value of node");
-    node.get_scripts().addScript(PathBuf::from_str(path).expect("Invalid Path"), &self.lua, this).expect("Cannot add script to node");
+  fn current_scene(&mut self) -> &mut Scene {
+    self.scenes.last_mut().expect("No scene loaded")
   }
 
-  pub async fn mainloop(&mut self) {
-    load_persistrent(&self.lua, &self.environment).expect("Cannot load Persistent Data");
-    if let Ok(func) = self.environment.get::<Function>("Setup") {
-      func.call::<()>(()).expect("Error during Engine Setup");
-    } else {
-      warn!("No Engine Setup function");
+  pub fn lua(&self) -> &Lua {
+    &self.lua
+  }
+
+  #[cfg(test)]
+  pub fn environment(&self) -> mlua::Table {
+    self.scenes.last().expect("No scene loaded").environment().clone()
+  }
+
+  pub fn root(&self) -> NodeHandle {
+    self.scenes.last().expect("No scene loaded").root()
+  }
+
+  pub fn get_node(&self, path: &str) -> Option<NodeHandle> {
+    let mut node: NodeHandle = self.root();
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+      node = node.get_child(name)?;
     }
+    Some(node)
+  }
 
-    self.children.foreach_child(|_, _ , child| {
-      child.load_scripts();
-    });
-
-    self.load_children();
-
-    self.children.foreach_child(|_, _ , child| {
-        child.setup();
-        let tmp= child.get_scripts().run_4all_envs(&self.lua, "Setup".into(), MultiValue::new());
-        if tmp.is_err() {
-          warn!("Error during setup in script");
-          println!("ERROR: {}", tmp.err().unwrap());
-        } else {
-          let tmp: Option<Table> = tmp.unwrap();
-          if let Some(this) = tmp {
-            child.from_lua(Value::Table(this)).expect("Cannot update properties of 'this'");
-          }
-        }
-      });
-    loop {
-      let dt: f32 = get_frame_time();
+  pub fn setup(&mut self) {
+    let lua: &Lua = &self.lua;
+    self.scenes.last_mut().expect("No scene loaded").setup(lua);
+  }
 
-      load_persistrent(&self.lua, &self.environment).expect("Cannot load Persistent Data");
-      if let Ok(func) = self.environment.get::<Function>("Loop") {
-        func.call::<()>(dt).expect("Error during Engine Loop");
-      } else {
-        warn!("No Engine Loop function");
-      }
+  fn run_fixed_steps(&mut self, dt: f32) {
+    let Some(fixed_dt) = self.fixed_dt else {
+      return;
+    };
+    self.accumulator += dt;
+    let mut steps: u32 = 0;
+    while self.accumulator >= fixed_dt && steps < MAX_FIXED_STEPS {
+      let lua: &Lua = &self.lua;
+      let scene: &mut Scene = self.scenes.last_mut().expect("No scene loaded");
+      scene.fixed_step(lua, fixed_dt);
+      scene.physics_step(fixed_dt);
+      self.accumulator -= fixed_dt;
+      steps += 1;
+    }
+    if steps > 0 {
+      self.current_scene().settle_transforms();
+    }
+    if steps == MAX_FIXED_STEPS {
+      self.accumulator %= fixed_dt;
+    }
+    *RENDER_ALPHA.write().unwrap() = self.accumulator / fixed_dt;
+  }
+
+  fn open_scene(&mut self, scene: Scene) {
+    self.scenes.push(scene);
+    self.reload_settings();
+    self.setup();
+  }
+
+  fn close_scene(&mut self) {
+    if let Some(mut scene) = self.scenes.pop() {
+      scene.teardown(&self.lua);
+    }
+  }
 
-      let tmp = dt;
-      let lua_temp: Lua = std::mem::take(&mut self.lua);
-      self.children.foreach_child(|_, _ , child| {
-          child.update(tmp);
-          
-          let tmp: Result<Option<Table>, Box<dyn Error>>  = child.get_scripts().run_4all_envs(&lua_temp, "Loop".into(), MultiValue::from_vec(vec![Value::Number(dt as f64)]));
-          if tmp.is_err() {
-            warn!("Error during loop in script");
-            eprintln!("ERROR: {}", tmp.err().unwrap());
+  fn apply_scene_requests(&mut self) {
+    for request in take_scene_requests() {
+      match request {
+        //? The new scene is loaded before the old ones close, so a bad path leaves the game where it was.
+        SceneRequest::Change(ref path) | SceneRequest::Push(ref path) => {
+          let push: bool = matches!(request, SceneRequest::Push(_));
+          let scene: Scene = match Scene::load(&self.lua, path) {
+            Ok(scene) => scene,
+            Err(err) => {
+              warn!("Cannot load scene {}: {}", path, err);
+              continue
+            }
+          };
+          if push {
+            self.current_scene().camera = MAIN_CAMERA.read().unwrap().clone();
           } else {
-            let tmp: Option<Table> = tmp.unwrap();
-            if let Some(this) = tmp {
-              child.from_lua(Value::Table(this)).expect("Cannot update properties of 'this'");
+            while !self.scenes.is_empty() {
+              self.close_scene();
             }
+            MAIN_CAMERA.write().unwrap().take();
+            refresh_camera();
+          }
+          self.open_scene(scene);
+        },
+        SceneRequest::Pop => {
+          if self.scenes.len() > 1 {
+            self.close_scene();
+            self.reload_settings();
+            *MAIN_CAMERA.write().unwrap() = self.current_scene().camera.take();
+            refresh_camera();
+          } else {
+            warn!("Cannot pop the last scene");
           }
-        });
-      self.lua = lua_temp;
+        },
+      }
+    }
+  }
+
+  pub fn step(&mut self, dt: f32) {
+    refresh_camera();
+    self.run_fixed_steps(dt);
+
+    let lua: &Lua = &self.lua;
+    let scene: &mut Scene = self.scenes.last_mut().expect("No scene loaded");
+    scene.step(lua, dt);
+    if self.fixed_dt.is_none() {
+      scene.physics_step(dt);
+    }
+    scene.dispatch_collisions(lua);
+
+    let mut cameras: Vec<NodeHandle> = MAIN_CAMERA.read().unwrap().iter().cloned().collect();
+    for camera in scene.viewport_cameras() {
+      if !cameras.iter().any(|other| other.same(&camera)) {
+        cameras.push(camera);
+      }
+    }
+    for camera in cameras {
+      update_camera(&camera, dt);
+    }
+
+    self.apply_scene_requests();
+  }
+
+  pub fn render(&mut self) {
+    let bg: Color = self.scenes.first().and_then(|scene| scene.bg_color).unwrap_or(self.bg_color);
+    begin_frame(bg);
+    refresh_camera();
+    for scene in self.scenes.iter_mut() {
+      scene.render();
+    }
+    end_frame();
+  }
 
-      self.load_children();
+  pub fn run_frames(&mut self, frames: u64, dt: f32) -> u64 {
+    if !self.current_scene().is_ready() {
+      self.setup();
+    }
+    for frame in 0..frames {
+      if quit_requested() {
+        return frame;
+      }
+      self.step(dt);
+    }
+    frames
+  }
 
-      clear_background(self.bg_color.into());
-      self.children.foreach_child(|_, _ , child| {
-        child.render();
-      });
+  pub async fn mainloop(&mut self) {
+    self.setup();
+    loop {
+      self.step(get_frame_time());
+      self.render();
       next_frame().await;
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::core::{nodes::rectmesh::RectMesh, physics::gravity, proxy::WeakNodeHandle, testing::Headless};
+
+  #[test]
+  fn headless_run_moves_nodes_and_stops_on_quit() {
+    let mut engine: Headless = Headless::load("headless.lua");
+    assert_eq!(engine.run_frames(60, 1.0 / 60.0), 10);
+    let player: NodeHandle = engine.get_node("player").expect("No player node");
+    assert_eq!(player.cast(|mesh: &mut RectMesh| mesh.transform.pos), Some(Vec2::new(20.0, 0.0)));
+    assert!(engine.get_node("player/missing").is_none());
+  }
+
+  #[test]
+  fn quitting_does_not_stop_the_next_engine() {
+    let mut engine: Headless = Headless::load("headless.lua");
+    assert_eq!(engine.run_frames(60, 1.0 / 60.0), 10);
+    drop(engine);
+    let mut engine: Headless = Headless::load("headless.lua");
+    assert!(is_headless());
+    assert_eq!(engine.run_frames(5, 1.0 / 60.0), 5);
+    assert_eq!(engine.eval::<u32>("return frames"), 5);
+  }
+
+  #[test]
+  fn switching_scenes_frees_the_old_tree_and_applies_its_settings() {
+    let mut engine: Headless = Headless::load("scenes_menu.lua");
+    engine.run_frames(1, 1.0 / 60.0);
+    let hero: WeakNodeHandle = engine.get_node("hero").expect("No hero node").downgrade();
+    assert_eq!(gravity(), Vec2::new(0.0, 100.0));
+    assert_eq!(engine.fixed_dt, None);
+
+    engine.run_frames(1, 1.0 / 60.0);
+    engine.lua().gc_collect().unwrap();
+    assert!(hero.upgrade().is_none());
+    assert!(engine.get_node("floor").is_some());
+    assert_eq!(gravity(), Vec2::new(0.0, 50.0));
+    assert_eq!(engine.fixed_dt, Some(1.0 / 30.0));
+
+    engine.eval::<mlua::Value>("push_scene('tests/fixtures/scenes_pause.lua')");
+    engine.run_frames(1, 1.0 / 60.0);
+    assert!(engine.get_node("floor").is_none());
+    assert_eq!(gravity(), Vec2::new(0.0, 980.0));
+    assert_eq!(engine.fixed_dt, None);
+
+    engine.eval::<mlua::Value>("pop_scene()");
+    engine.run_frames(1, 1.0 / 60.0);
+    assert!(engine.get_node("floor").is_some());
+    assert_eq!(gravity(), Vec2::new(0.0, 50.0));
+    assert_eq!(engine.fixed_dt, Some(1.0 / 30.0));
+  }
+
+  #[test]
+  fn fixed_steps_follow_the_accumulator_and_stop_at_the_clamp() {
+    let mut engine: Headless = Headless::load("fixed.lua");
+    engine.run_frames(1, 0.25);
+    assert_eq!(engine.eval::<u32>("return ticks"), 2);
+    assert!((render_alpha() - 0.5).abs() < 1e-4);
+    engine.run_frames(1, 0.07);
+    assert_eq!(engine.eval::<u32>("return ticks"), 3);
+    engine.run_frames(1, 5.0);
+    assert_eq!(engine.eval::<u32>("return ticks"), 3 + MAX_FIXED_STEPS);
+    assert!(engine.accumulator < 0.1);
+  }
+
+  #[test]
+  fn only_nodes_left_by_the_fixed_step_are_interpolated() {
+    let mut engine: Headless = Headless::load("fixed.lua");
+    engine.run_frames(1, 0.25);
+    let interpolated = |name: &str| engine.get_node(name).and_then(|node| node.cast(|mesh: &mut RectMesh| mesh.transform.interpolated_pos()));
+    let glider: Vec2 = interpolated("glider").expect("No glider node");
+    assert!((glider.get_x() - 15.0).abs() < 1e-3);
+    assert_eq!(interpolated("mover"), Some(Vec2::new(5.0, 50.0)));
+  }
+}
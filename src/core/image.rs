@@ -1,12 +1,13 @@
 use futures::executor::block_on;
 use macroquad::{math::Rect, texture::{DrawTextureParams, Texture2D, draw_texture_ex, load_texture}};
-use mlua::{AnyUserData, Value};
+use mlua::{FromLua, IntoLua, Lua, MetaMethod, UserData, UserDataMethods, Value};
 
-use crate::core::{color::Color, core::{LuaTexture, Luable, radians}, vec2::Vec2};
+use crate::core::{color::Color, core::radians, engine::is_headless, proxy::Place, transform::ScreenRect, vec2::Vec2};
 
 
+#[derive(Clone)]
 pub struct Img {
-  texture: Texture2D,
+  texture: Option<Texture2D>,
   rotation: f32,
   src: Option<Vec2>,
   tint: Color,
@@ -17,7 +18,11 @@ pub struct Img {
 impl Img {
   pub fn new(path: &str) -> Img {
     Img { 
-      texture: block_on(load_texture(path)).expect(&format!("Cannot load texture {}", path)), 
+      texture: if is_headless() {
+        None
+      } else {
+        Some(block_on(load_texture(path)).expect(&format!("Cannot load texture {}", path)))
+      }, 
       rotation: 0.0, 
       src: None,
       tint: Color::new(0xffffffff),
@@ -26,6 +31,10 @@ impl Img {
     }
   }
 
+  pub fn from_texture(texture: Option<Texture2D>) -> Img {
+    Img { texture, rotation: 0.0, src: None, tint: Color::new(0xffffffff), flip_x: false, flip_y: false }
+  }
+
   pub fn with_degrees(mut self, degrees: f32) -> Self {
     self.rotation = radians(degrees);
     self
@@ -47,60 +56,91 @@ impl Img {
     self.tint = col;
     self
   }
-  pub fn render(&self, pos: Vec2, size: Vec2) {
+  pub fn render(&self, rect: &ScreenRect) {
+    let Some(texture) = self.texture.as_ref() else {
+      return;
+    };
+    let size: Vec2 = rect.size;
+    let center: Vec2 = rect.pos + ((Vec2::new(0.5, 0.5) - rect.origin) * size).rotated(rect.rotation);
+    let pos: Vec2 = center - size / 2.0;
     draw_texture_ex(
-      &self.texture, 
-      pos.get_x() as f32, 
-      pos.get_y() as f32, 
+      texture, 
+      pos.get_x(), 
+      pos.get_y(), 
       self.tint.into(), 
       DrawTextureParams {
-        dest_size: Some(macroquad::math::Vec2 { x: size.get_x() as f32, y: size.get_y() as f32 }),
-        rotation: self.rotation,
+        dest_size: Some(macroquad::math::Vec2 { x: size.get_x(), y: size.get_y() }),
+        rotation: self.rotation + rect.rotation,
         source: match self.src {
-          Some(v) => Some(Rect::new(v.get_x() as f32, v.get_y() as f32, size.get_x() as f32, size.get_y() as f32)),
+          Some(v) => Some(Rect::new(v.get_x(), v.get_y(), size.get_x(), size.get_y())),
           None => None
         },
         flip_x: self.flip_x,
         flip_y: self.flip_y,
-        pivot: None
+        pivot: Some(center.into())
       }
     );
   }
 }
 
-impl Luable for Img {
-  fn as_lua(&self, lua: &mlua::Lua) -> Result<mlua::Value, Box<dyn std::error::Error>> {
-    let table: mlua::Table = lua.create_table()?;
-    table.set("rotation", self.rotation)?;
-    table.set("src", match self.src {
-      Some(vec) => vec.as_lua(lua)?,
-      None => Value::Nil
-    })?;
-    table.set("tint", self.tint.as_lua(lua)?)?;
-    table.set("flip_x", self.flip_x)?;
-    table.set("flip_y", self.flip_y)?;
-    table.set("texture", lua.create_userdata(LuaTexture(self.texture.clone()))?)?;
-    Ok(Value::Table(table))
+impl FromLua for Img {
+  fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+    match &value {
+      Value::UserData(ud) if ud.is::<Place<Img>>() => Ok(ud.borrow::<Place<Img>>()?.get()),
+      _ => Err(mlua::Error::FromLuaConversionError { from: value.type_name(), to: "Img".to_string(), message: None })
+    }
+  }
+}
+
+impl IntoLua for Img {
+  fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+    Place::owned(self).into_lua(lua)
   }
+}
+
+impl UserData for Place<Img> {
+  fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+    methods.add_meta_method(MetaMethod::Index, |lua, this, key: String| {
+      match key.as_str() {
+        "rotation" => this.get().rotation.into_lua(lua),
+        "src" => this.get().src.into_lua(lua),
+        "tint" => this.map(|img| &mut img.tint).into_lua(lua),
+        "flip_x" => this.get().flip_x.into_lua(lua),
+        "flip_y" => this.get().flip_y.into_lua(lua),
+        _ => Ok(Value::Nil)
+      }
+    });
 
-  fn from_lua(&mut self, value: Value) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(table) = value.as_table() {
-      self.rotation = table.get("rotation")?;
-      self.src = match table.get::<Value>("src")? {
-        Value::Table(tbl) => {
-          let mut tmp = Vec2::new(0, 0);
-          tmp.from_lua(Value::Table(tbl))?;
-          Some(tmp)
+    methods.add_meta_method(MetaMethod::NewIndex, |lua, this, (key, value): (String, Value)| {
+      match key.as_str() {
+        "rotation" => {
+          let rotation: f32 = f32::from_lua(value, lua)?;
+          this.with(|img| img.rotation = rotation);
         },
-        _ => None
-      };
-      self.tint.from_lua(table.get("tint")?)?;
-      self.flip_x = table.get("flip_x")?;
-      self.flip_y = table.get("flip_y")?;
-      let temp: AnyUserData = table.get("texture")?;
-      self.texture = temp.borrow::<LuaTexture>()?.0.clone();
-      return Ok(())
-    }
-    Err("Invalid Lua Value".into())
+        "src" => {
+          let src: Option<Vec2> = Option::<Vec2>::from_lua(value, lua)?;
+          this.with(|img| img.src = src);
+        },
+        "tint" => {
+          let tint: Color = Color::from_lua(value, lua)?;
+          this.with(|img| img.tint = tint);
+        },
+        "flip_x" => {
+          let flip: bool = bool::from_lua(value, lua)?;
+          this.with(|img| img.flip_x = flip);
+        },
+        "flip_y" => {
+          let flip: bool = bool::from_lua(value, lua)?;
+          this.with(|img| img.flip_y = flip);
+        },
+        _ => return Err(mlua::Error::RuntimeError(format!("Img has no field '{}'", key)))
+      }
+      Ok(())
+    });
+
+    methods.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+      let img: Img = this.get();
+      Ok(format!("Img(rotation: {}, flip_x: {}, flip_y: {})", img.rotation, img.flip_x, img.flip_y))
+    });
   }
 }
@@ -0,0 +1,181 @@
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+use mlua::{FromLua, IntoLua, Lua, MetaMethod, UserData, UserDataMethods, Value};
+
+use crate::core::{proxy::{Place, method}, vec2::Vec2};
+
+//? Integer vector for tile and grid coordinates. Division rounds towards negative infinity, so cells left of 0 stay consistent.
+//? Arithmetic wraps on overflow like Lua integers; the Lua division metamethods reject a zero divisor before it gets here.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct IVec2 {
+  x: i32,
+  y: i32
+}
+
+impl IVec2 {
+  pub const ZERO: IVec2 = Self {x: 0, y: 0};
+  pub const ONE: IVec2 = Self {x: 1, y: 1};
+
+  pub fn new(x: i32, y: i32) -> Self {
+    Self {x, y}
+  }
+
+  pub fn dot(self, other: Self) -> i32 {
+    self.x.wrapping_mul(other.x).wrapping_add(self.y.wrapping_mul(other.y))
+  }
+
+  pub fn get_x(&self) -> i32 {
+    self.x
+  }
+
+  pub fn get_y(&self) -> i32 {
+    self.y
+  }
+}
+
+impl Add for IVec2 {
+  type Output = Self;
+  fn add(self, rhs: Self) -> Self::Output {
+    IVec2::new(self.x.wrapping_add(rhs.x), self.y.wrapping_add(rhs.y))
+  }
+}
+impl Sub for IVec2 {
+  type Output = Self;
+  fn sub(self, rhs: Self) -> Self::Output {
+    IVec2::new(self.x.wrapping_sub(rhs.x), self.y.wrapping_sub(rhs.y))
+  }
+}
+impl Mul for IVec2 {
+  type Output = Self;
+  fn mul(self, rhs: Self) -> Self::Output {
+    IVec2::new(self.x.wrapping_mul(rhs.x), self.y.wrapping_mul(rhs.y))
+  }
+}
+impl Mul<i32> for IVec2 {
+  type Output = Self;
+  fn mul(self, rhs: i32) -> Self::Output {
+    IVec2::new(self.x.wrapping_mul(rhs), self.y.wrapping_mul(rhs))
+  }
+}
+impl Div for IVec2 {
+  type Output = Self;
+  fn div(self, rhs: Self) -> Self::Output {
+    IVec2::new(self.x.wrapping_div_euclid(rhs.x), self.y.wrapping_div_euclid(rhs.y))
+  }
+}
+impl Div<i32> for IVec2 {
+  type Output = Self;
+  fn div(self, rhs: i32) -> Self::Output {
+    IVec2::new(self.x.wrapping_div_euclid(rhs), self.y.wrapping_div_euclid(rhs))
+  }
+}
+impl Neg for IVec2 {
+  type Output = Self;
+  fn neg(self) -> Self::Output {
+    IVec2::new(self.x.wrapping_neg(), self.y.wrapping_neg())
+  }
+}
+
+impl AddAssign for IVec2 {
+  fn add_assign(&mut self, rhs: Self) {
+    *self = *self + rhs;
+  }
+}
+impl SubAssign for IVec2 {
+  fn sub_assign(&mut self, rhs: Self) {
+    *self = *self - rhs;
+  }
+}
+
+impl FromLua for IVec2 {
+  fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+    match &value {
+      Value::UserData(ud) if ud.is::<Place<IVec2>>() => Ok(ud.borrow::<Place<IVec2>>()?.get()),
+      Value::Table(table) => Ok(IVec2::new(table.get("x")?, table.get("y")?)),
+      _ => Err(mlua::Error::FromLuaConversionError { from: value.type_name(), to: "IVec2".to_string(), message: None })
+    }
+  }
+}
+
+impl IntoLua for IVec2 {
+  fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+    Place::owned(self).into_lua(lua)
+  }
+}
+
+enum Operand {
+  Vector(IVec2),
+  Scalar(i32),
+}
+
+impl Operand {
+  fn from_lua(value: Value, lua: &Lua) -> mlua::Result<Operand> {
+    match value {
+      Value::Integer(i) => Ok(Operand::Scalar(i as i32)),
+      Value::Number(n) if n.fract() == 0.0 => Ok(Operand::Scalar(n as i32)),
+      Value::Number(_) => Err(mlua::Error::RuntimeError("IVec2 only works with whole numbers, use Vec2 instead".to_string())),
+      other => Ok(Operand::Vector(IVec2::from_lua(other, lua)?)),
+    }
+  }
+}
+
+impl UserData for Place<IVec2> {
+  fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+    methods.add_meta_method(MetaMethod::Index, |lua, this, key: String| {
+      match key.as_str() {
+        "x" => this.get().x.into_lua(lua),
+        "y" => this.get().y.into_lua(lua),
+        "dot" => method(lua, "IVec2.dot", |_, (this, other): (IVec2, IVec2)| {
+          Ok(this.dot(other))
+        }),
+        "to_vec2" => method(lua, "IVec2.to_vec2", |_, this: IVec2| {
+          Ok(Vec2::from(this))
+        }),
+        _ => Ok(Value::Nil)
+      }
+    });
+
+    methods.add_meta_method(MetaMethod::NewIndex, |_, this, (key, value): (String, i32)| {
+      match key.as_str() {
+        "x" => this.with(|vec| vec.x = value),
+        "y" => this.with(|vec| vec.y = value),
+        _ => return Err(mlua::Error::RuntimeError(format!("IVec2 has no field '{}'", key)))
+      }
+      Ok(())
+    });
+
+    methods.add_meta_function(MetaMethod::Add, |lua, (a, b): (IVec2, IVec2)| {
+      (a + b).into_lua(lua)
+    });
+    methods.add_meta_function(MetaMethod::Sub, |lua, (a, b): (IVec2, IVec2)| {
+      (a - b).into_lua(lua)
+    });
+    methods.add_meta_function(MetaMethod::Mul, |lua, (a, b): (Value, Value)| {
+      match (Operand::from_lua(a, lua)?, Operand::from_lua(b, lua)?) {
+        (Operand::Vector(a), Operand::Vector(b)) => (a * b).into_lua(lua),
+        (Operand::Vector(v), Operand::Scalar(s)) | (Operand::Scalar(s), Operand::Vector(v)) => (v * s).into_lua(lua),
+        (Operand::Scalar(a), Operand::Scalar(b)) => a.wrapping_mul(b).into_lua(lua),
+      }
+    });
+    for meta in [MetaMethod::Div, MetaMethod::IDiv] {
+      methods.add_meta_function(meta, |lua, (a, b): (IVec2, Value)| {
+        match Operand::from_lua(b, lua)? {
+          Operand::Vector(b) if b.x == 0 || b.y == 0 => Err(mlua::Error::RuntimeError("IVec2 cannot be divided by zero".to_string())),
+          Operand::Scalar(0) => Err(mlua::Error::RuntimeError("IVec2 cannot be divided by zero".to_string())),
+          Operand::Vector(b) => (a / b).into_lua(lua),
+          Operand::Scalar(s) => (a / s).into_lua(lua),
+        }
+      });
+    }
+    methods.add_meta_function(MetaMethod::Unm, |lua, a: IVec2| {
+      (-a).into_lua(lua)
+    });
+    methods.add_meta_function(MetaMethod::Eq, |_, (a, b): (IVec2, IVec2)| {
+      Ok(a == b)
+    });
+    methods.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+      let vec: IVec2 = this.get();
+      Ok(format!("IVec2({}, {})", vec.x, vec.y))
+    });
+  }
+}
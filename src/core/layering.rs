@@ -0,0 +1,99 @@
+use std::{cmp::Ordering, sync::{Arc, RwLock}};
+
+use lazy_static::lazy_static;
+use mlua::{FromLua, IntoLua, Lua, Table, Value};
+
+pub const DEFAULT_LAYER: &str = "default";
+
+pub struct RenderLayer {
+  pub name: String,
+  pub y_sort: bool,
+}
+
+lazy_static! {
+  static ref RENDER_LAYERS: Arc<RwLock<Vec<RenderLayer>>> = Arc::new(RwLock::new(Vec::new()));
+}
+
+//? The default layer is drawn before every declared layer unless it is declared itself.
+pub fn load_render_layers(env: &Table) -> mlua::Result<()> {
+  let mut layers: Vec<RenderLayer> = Vec::new();
+  if let Some(tbl) = env.get::<Option<Table>>("RenderLayers")? {
+    for entry in tbl.sequence_values::<Value>() {
+      let layer: RenderLayer = match entry? {
+        Value::String(name) => RenderLayer { name: name.to_str()?.to_string(), y_sort: false },
+        Value::Table(tbl) => RenderLayer { name: tbl.get("name")?, y_sort: tbl.get::<Option<bool>>("y_sort")?.unwrap_or(false) },
+        other => return Err(mlua::Error::RuntimeError(format!("Invalid render layer: {}", other.type_name())))
+      };
+      layers.push(layer);
+    }
+  }
+  *RENDER_LAYERS.write().unwrap() = layers;
+  Ok(())
+}
+
+pub fn layer_exists(name: &str) -> bool {
+  name == DEFAULT_LAYER || RENDER_LAYERS.read().unwrap().iter().any(|layer| layer.name == name)
+}
+
+fn layer_info(name: &str) -> (i64, bool) {
+  let layers = RENDER_LAYERS.read().unwrap();
+  match layers.iter().position(|layer| layer.name == name) {
+    Some(index) => (index as i64, layers[index].y_sort),
+    None => (-1, false)
+  }
+}
+
+#[derive(Clone)]
+pub struct Layering {
+  pub z_index: i32,
+  pub layer: String,
+}
+
+impl Layering {
+  pub fn new() -> Layering {
+    Layering { z_index: 0, layer: DEFAULT_LAYER.to_string() }
+  }
+
+  pub fn lua_get(&self, lua: &Lua, key: &str) -> mlua::Result<Value> {
+    match key {
+      "z_index" => self.z_index.into_lua(lua),
+      "layer" => self.layer.clone().into_lua(lua),
+      _ => Ok(Value::Nil)
+    }
+  }
+
+  pub fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "z_index" => self.z_index = i32::from_lua(value, lua)?,
+      "layer" => {
+        let layer: String = String::from_lua(value, lua)?;
+        if !layer_exists(&layer) {
+          return Err(mlua::Error::RuntimeError(format!("Unknown render layer '{}', declare it in RenderLayers", layer)))
+        }
+        self.layer = layer;
+      },
+      _ => return Ok(false)
+    }
+    Ok(true)
+  }
+}
+
+//? Ties keep tree order, since draw calls are sorted with a stable sort.
+pub struct DrawKey {
+  layer: i64,
+  z_index: i32,
+  y: f32,
+}
+
+impl DrawKey {
+  pub fn new(layering: &Layering, y: f32) -> DrawKey {
+    let (layer, y_sort) = layer_info(&layering.layer);
+    DrawKey { layer, z_index: layering.z_index, y: if y_sort { y } else { 0.0 } }
+  }
+
+  pub fn compare(&self, other: &DrawKey) -> Ordering {
+    self.layer.cmp(&other.layer)
+      .then(self.z_index.cmp(&other.z_index))
+      .then(self.y.total_cmp(&other.y))
+  }
+}
@@ -1,12 +1,24 @@
 
 pub mod vec2;
+pub mod ivec2;
 pub mod color;
 pub mod children_container;
 pub mod script_manager;
 pub mod transform;
+pub mod shape;
 pub mod keys;
 pub mod image;
 pub mod core;
 pub mod nodelike;
 pub mod nodes;
 pub mod engine;
+pub mod scene;
+pub mod proxy;
+pub mod layering;
+pub mod collision_layers;
+pub mod stretch;
+pub mod physics;
+pub mod broadphase;
+pub mod query;
+#[cfg(test)]
+pub mod testing;
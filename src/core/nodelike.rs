@@ -1,6 +1,8 @@
 use std::{any::Any, sync::atomic::{AtomicU64, Ordering}};
 
-use crate::core::{core::{Downcastable, Luable}, script_manager::ScriptManager};
+use mlua::{Lua, Value};
+
+use crate::core::{core::Downcastable, layering::Layering, nodes::node::Node, proxy::NodeHandle, script_manager::ScriptManager, transform::{ParentFrame, Transform}, vec2::Vec2};
 
 static NEXT_ID: AtomicU64 = AtomicU64::new(0);
 
@@ -8,13 +10,42 @@ pub fn generate_id() -> u64 {
   NEXT_ID.fetch_add(1, Ordering::Relaxed)
 }
 
-pub trait NodeLike: Downcastable + Luable + Send + Sync {
+pub trait NodeLike: Downcastable + Send + Sync {
   fn setup(&mut self);
   fn update(&mut self, deltatime: f32);
   fn render(&mut self);
-  fn load_scripts(&mut self);
+  fn teardown(&mut self);
   fn get_scripts(&mut self) -> &mut ScriptManager;
+  fn get_base(&mut self) -> &mut Node;
   fn get_kind(&self) -> &str;
+  fn get_id(&self) -> u64;
+  fn get_transform(&mut self) -> Option<&mut Transform> {
+    None
+  }
+  fn get_position(&mut self) -> Option<&mut Vec2> {
+    self.get_transform().map(|transform| &mut transform.pos)
+  }
+  fn propagate(&mut self, parent: ParentFrame) -> ParentFrame {
+    match self.get_transform() {
+      Some(transform) => {
+        transform.parent = parent;
+        transform.child_frame()
+      },
+      None => parent
+    }
+  }
+  fn get_layering(&mut self) -> Option<&mut Layering> {
+    None
+  }
+  fn sort_y(&mut self) -> f32 {
+    match self.get_transform() {
+      Some(transform) => transform.corners().iter().map(|corner| corner.get_y()).fold(f32::NEG_INFINITY, f32::max),
+      None => 0.0
+    }
+  }
+  fn lua_keys(&self) -> Vec<&'static str>;
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value>;
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool>;
 }
 
 impl Downcastable for Box<dyn NodeLike + Send + Sync> {
@@ -1,10 +1,11 @@
-use mlua::Value;
+use mlua::{IntoLua, Lua, Value};
 
-use crate::core::{color::Color, core::{Downcastable, Luable}, image::Img, nodelike::NodeLike, nodes::{clickable_area::ClickableArea, node::Node, sprite::Sprite, text::Text}, vec2::Vec2};
+use crate::core::{color::Color, core::Downcastable, image::Img, layering::Layering, nodelike::NodeLike, nodes::{clickable_area::ClickableArea, node::Node, sprite::Sprite, text::Text}, proxy::NodeHandle, script_manager::ScriptManager, transform::ParentFrame, vec2::Vec2};
 
 
 pub struct TextButton {
   base: Node,
+  pub layering: Layering,
   text: Text,
   area: ClickableArea
 }
@@ -13,7 +14,7 @@ impl TextButton {
   pub fn new(text: &str, pos: Vec2, size: u16, color: Color) -> TextButton {
     let temp = Text::new(text, pos, size, color);
     let size = (&temp).getTextSize();
-    TextButton { base: Node::new(), text: temp, area: ClickableArea::new(pos, size) }
+    TextButton { base: Node::new(), layering: Layering::new(), text: temp, area: ClickableArea::new(pos, size) }
   }
 }
 
@@ -21,14 +22,24 @@ impl NodeLike for TextButton {
   fn get_kind(&self) -> &str {
     "TextButton"
   }
-  fn get_scripts(&mut self) -> &mut crate::core::script_manager::ScriptManager {
+  fn get_id(&self) -> u64 {
+    self.base.id
+  }
+  fn get_scripts(&mut self) -> &mut ScriptManager {
     self.base.get_scripts()
   }
-  fn load_scripts(&mut self) {
-    let tmp = self.get_kind().to_string();
-    self.base.load_scripts(&tmp);
-    self.area.load_scripts();
-    self.text.load_scripts();
+  fn get_base(&mut self) -> &mut Node {
+    &mut self.base
+  }
+  fn get_layering(&mut self) -> Option<&mut Layering> {
+    Some(&mut self.layering)
+  }
+  fn sort_y(&mut self) -> f32 {
+    self.area.sort_y()
+  }
+  fn propagate(&mut self, parent: ParentFrame) -> ParentFrame {
+    self.text.propagate(parent);
+    self.area.propagate(parent)
   }
   fn render(&mut self) {
     self.base.render();
@@ -40,12 +51,36 @@ impl NodeLike for TextButton {
     self.area.setup();
     self.text.setup();
   }
+  fn teardown(&mut self) {
+    self.base.teardown();
+    self.area.teardown();
+    self.text.teardown();
+  }
   fn update(&mut self, deltatime: f32) {
     self.base.update(deltatime);
     self.area.update(deltatime);
     self.text.update(deltatime);
     self.area.transform.size = self.text.getTextSize();
   }
+  fn lua_keys(&self) -> Vec<&'static str> {
+    vec!["area", "text", "z_index", "layer"]
+  }
+  //? The embedded nodes are handed out as handles into this button, so edits through them land here.
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    match key {
+      "area" => this.sub(|btn: &mut TextButton| &mut btn.area).into_lua(lua),
+      "text" => this.sub(|btn: &mut TextButton| &mut btn.text).into_lua(lua),
+      "z_index" | "layer" => self.layering.lua_get(lua, key),
+      _ => self.base.lua_get_common(lua, this, self.get_kind(), key)
+    }
+  }
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "z_index" | "layer" => self.layering.lua_set(lua, key, value),
+      "area" | "text" => Err(mlua::Error::RuntimeError(format!("TextButton.{} cannot be replaced, edit its fields instead", key))),
+      _ => Ok(false)
+    }
+  }
 }
 
 impl Downcastable for TextButton {
@@ -54,37 +89,17 @@ impl Downcastable for TextButton {
   }
 }
 
-impl Luable for TextButton {
-  fn as_lua(&self, lua: &mlua::Lua) -> Result<mlua::Value, Box<dyn std::error::Error>> {
-    let table = lua.create_table()?;
-
-    table.set("base", self.base.as_lua(lua)?)?;
-    table.set("area", self.area.as_lua(lua)?)?;
-    table.set("text", self.text.as_lua(lua)?)?;
-
-    Node::add_kind_to_lua(self.get_kind().to_string(), &table, lua)?;
-
-    Ok(Value::Table(table))
-  }
-  fn from_lua(&mut self, value: Value) -> Result<(), Box<dyn std::error::Error>> {
-    let table = value.as_table().ok_or("Invalid Lua Value")?;
-    self.base.from_lua(table.get("base")?)?;
-    self.area.from_lua(table.get("area")?)?;
-    self.text.from_lua(table.get("text")?)?;
-    Ok(())
-  }
-}
-
 
 pub struct SpriteButton {
   base: Node,
+  pub layering: Layering,
   sprite: Sprite,
   area: ClickableArea
 }
 
 impl SpriteButton {
   pub fn new(pos: Vec2, size: Vec2, img: Img) -> SpriteButton {
-    SpriteButton { base: Node::new(), sprite: Sprite::new(pos, size, img), area: ClickableArea::new(pos, size) }
+    SpriteButton { base: Node::new(), layering: Layering::new(), sprite: Sprite::new(pos, size, img), area: ClickableArea::new(pos, size) }
   }
 }
 
@@ -92,14 +107,24 @@ impl NodeLike for SpriteButton {
   fn get_kind(&self) -> &str {
     "SpriteButton"
   }
-  fn get_scripts(&mut self) -> &mut crate::core::script_manager::ScriptManager {
+  fn get_id(&self) -> u64 {
+    self.base.id
+  }
+  fn get_scripts(&mut self) -> &mut ScriptManager {
     self.base.get_scripts()
   }
-  fn load_scripts(&mut self) {
-    let tmp = self.get_kind().to_string();
-    self.base.load_scripts(&tmp);
-    self.area.load_scripts();
-    self.sprite.load_scripts();
+  fn get_base(&mut self) -> &mut Node {
+    &mut self.base
+  }
+  fn get_layering(&mut self) -> Option<&mut Layering> {
+    Some(&mut self.layering)
+  }
+  fn sort_y(&mut self) -> f32 {
+    self.area.sort_y()
+  }
+  fn propagate(&mut self, parent: ParentFrame) -> ParentFrame {
+    self.sprite.propagate(parent);
+    self.area.propagate(parent)
   }
   fn render(&mut self) {
     self.base.render();
@@ -111,11 +136,34 @@ impl NodeLike for SpriteButton {
     self.area.setup();
     self.sprite.setup();
   }
+  fn teardown(&mut self) {
+    self.base.teardown();
+    self.area.teardown();
+    self.sprite.teardown();
+  }
   fn update(&mut self, deltatime: f32) {
     self.base.update(deltatime);
     self.area.update(deltatime);
     self.sprite.update(deltatime);
   }
+  fn lua_keys(&self) -> Vec<&'static str> {
+    vec!["area", "sprite", "z_index", "layer"]
+  }
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    match key {
+      "area" => this.sub(|btn: &mut SpriteButton| &mut btn.area).into_lua(lua),
+      "sprite" => this.sub(|btn: &mut SpriteButton| &mut btn.sprite).into_lua(lua),
+      "z_index" | "layer" => self.layering.lua_get(lua, key),
+      _ => self.base.lua_get_common(lua, this, self.get_kind(), key)
+    }
+  }
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "z_index" | "layer" => self.layering.lua_set(lua, key, value),
+      "area" | "sprite" => Err(mlua::Error::RuntimeError(format!("SpriteButton.{} cannot be replaced, edit its fields instead", key))),
+      _ => Ok(false)
+    }
+  }
 }
 
 impl Downcastable for SpriteButton {
@@ -123,24 +171,3 @@ impl Downcastable for SpriteButton {
     self
   }
 }
-
-impl Luable for SpriteButton {
-  fn as_lua(&self, lua: &mlua::Lua) -> Result<mlua::Value, Box<dyn std::error::Error>> {
-    let table = lua.create_table()?;
-
-    table.set("base", self.base.as_lua(lua)?)?;
-    table.set("area", self.area.as_lua(lua)?)?;
-    table.set("sprite", self.sprite.as_lua(lua)?)?;
-
-    Node::add_kind_to_lua(self.get_kind().to_string(), &table, lua)?;
-
-    Ok(Value::Table(table))
-  }
-  fn from_lua(&mut self, value: Value) -> Result<(), Box<dyn std::error::Error>> {
-    let table = value.as_table().ok_or("Invalid Lua Value")?;
-    self.base.from_lua(table.get("base")?)?;
-    self.area.from_lua(table.get("area")?)?;
-    self.sprite.from_lua(table.get("sprite")?)?;
-    Ok(())
-  }
-}
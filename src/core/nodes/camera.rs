@@ -1,17 +1,130 @@
-use mlua::Value;
+use mlua::{FromLua, IntoLua, Lua, Value};
 
-use crate::core::{core::{Downcastable, Luable}, keys, nodelike::NodeLike, nodes::node::Node, transform::Transform, vec2::Vec2};
+use crate::core::{core::Downcastable, nodelike::NodeLike, nodes::node::Node, proxy::{NodeHandle, Place, WeakNodeHandle, method}, script_manager::ScriptManager, transform::Transform, vec2::Vec2};
 
+//? Keeps the view finite: 'focal_length' is the inverse of 'zoom'.
+const MIN_ZOOM: f32 = 0.01;
 
+//? With the default origin that is the top left corner of the view; zoom and rotation always turn around the view's center.
 pub struct Camera {
   base: Node,
-  pub focal_length: f32,
-  pub transform: Transform
+  pub zoom: f32,
+  pub transform: Transform,
+  target: Option<WeakNodeHandle>,
+  smoothing: f32,
+  deadzone: Vec2,
+  limit_min: Option<Vec2>,
+  limit_max: Option<Vec2>,
+  trauma: f32,
+  trauma_decay: f32,
+  shake_offset: Vec2,
+  shake_rotation: f32,
+  shake_time: f32,
+}
+
+//? What rendering needs from the main camera, copied out so nodes can draw without locking the camera node.
+#[derive(Clone)]
+pub struct CameraView {
+  pub center: Vec2,
+  pub zoom: f32,
+  pub rotation: f32,
+  pub screen_center: Vec2,
+}
+
+impl CameraView {
+  pub fn to_screen(&self, point: Vec2) -> Vec2 {
+    self.screen_center + (point - self.center).rotated(-self.rotation) * self.zoom
+  }
+
+  pub fn to_world(&self, point: Vec2) -> Vec2 {
+    self.center + ((point - self.screen_center) / self.zoom).rotated(self.rotation)
+  }
+}
+
+fn noise(time: f32, seed: f32) -> f32 {
+  (time * 31.0 + seed * 12.9898).sin() * 0.6 + (time * 17.3 + seed * 78.233).sin() * 0.4
 }
 
 impl Camera {
-  pub fn new(pos: Vec2, surface: Vec2, focal_length: f32) -> Camera {
-    Camera { base: Node::new(), focal_length, transform: Transform::new(pos, surface) }
+  pub fn new(pos: Vec2, surface: Vec2, zoom: f32) -> Camera {
+    Camera {
+      base: Node::new(),
+      zoom,
+      transform: Transform::new(pos, surface),
+      target: None,
+      smoothing: 0.0,
+      deadzone: Vec2::ZERO,
+      limit_min: None,
+      limit_max: None,
+      trauma: 0.0,
+      trauma_decay: 1.0,
+      shake_offset: Vec2::new(16.0, 16.0),
+      shake_rotation: 0.1,
+      shake_time: 0.0,
+    }
+  }
+
+  //? Older scripts zoom with 'focal_length', which divided the view's size instead of scaling it.
+  pub fn zoom_for(focal_length: f32) -> f32 {
+    if focal_length > 0.0 { (1.0 / focal_length).max(MIN_ZOOM) } else { 1.0 }
+  }
+
+  fn center_offset(&self) -> Vec2 {
+    (Vec2::new(0.5, 0.5) - self.transform.origin) * self.transform.size
+  }
+
+  pub fn target(&self) -> Option<NodeHandle> {
+    self.target.as_ref().and_then(|target| target.upgrade())
+  }
+
+  pub fn follow(&mut self, target: Vec2, deltatime: f32) {
+    let center: Vec2 = self.transform.global_pos() + self.center_offset();
+    let (half_x, half_y) = (self.deadzone.get_x() / 2.0, self.deadzone.get_y() / 2.0);
+    let step = |delta: f32, half: f32| if delta > half { delta - half } else if delta < -half { delta + half } else { 0.0 };
+    let delta: Vec2 = target - center;
+    let mut wanted: Vec2 = Vec2::new(step(delta.get_x(), half_x), step(delta.get_y(), half_y));
+    if self.smoothing > 0.0 {
+      wanted *= 1.0 - (-self.smoothing * deltatime).exp();
+    }
+    let global: Vec2 = self.transform.global_pos() + wanted;
+    self.transform.pos = self.transform.parent.to_local(global);
+  }
+
+  pub fn add_trauma(&mut self, amount: f32) {
+    self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+  }
+
+  pub fn decay_trauma(&mut self, deltatime: f32) {
+    self.trauma = (self.trauma - self.trauma_decay * deltatime).max(0.0);
+    self.shake_time = if self.trauma > 0.0 { self.shake_time + deltatime } else { 0.0 };
+  }
+
+  fn clamp_to_limits(&self, center: Vec2, half: Vec2) -> Vec2 {
+    let clamp = |value: f32, half: f32, min: Option<f32>, max: Option<f32>| match (min, max) {
+      (Some(min), Some(max)) if max - min < half * 2.0 => (min + max) / 2.0,
+      (min, max) => {
+        let value: f32 = max.map_or(value, |max| value.min(max - half));
+        min.map_or(value, |min| value.max(min + half))
+      }
+    };
+    Vec2::new(
+      clamp(center.get_x(), half.get_x(), self.limit_min.map(|v| v.get_x()), self.limit_max.map(|v| v.get_x())),
+      clamp(center.get_y(), half.get_y(), self.limit_min.map(|v| v.get_y()), self.limit_max.map(|v| v.get_y())),
+    )
+  }
+
+  pub fn view(&self) -> CameraView {
+    let zoom: f32 = if self.zoom > 0.0 { self.zoom } else { 1.0 };
+    let screen_center: Vec2 = self.transform.size / 2.0;
+    let center: Vec2 = self.clamp_to_limits(self.transform.interpolated_global_pos() + self.center_offset(), screen_center / zoom);
+    let shake: f32 = self.trauma * self.trauma;
+    let offset: Vec2 = self.shake_offset * Vec2::new(noise(self.shake_time, 1.0), noise(self.shake_time, 2.0)) * shake;
+    CameraView {
+      center: center + offset / zoom,
+      zoom,
+      rotation: self.transform.global_rotation() + self.shake_rotation * noise(self.shake_time, 3.0) * shake,
+      screen_center,
+    }
   }
 }
 
@@ -19,12 +132,17 @@ impl NodeLike for Camera {
   fn get_kind(&self) -> &str {
     "Camera"
   }
-  fn get_scripts(&mut self) -> &mut crate::core::script_manager::ScriptManager {
+  fn get_id(&self) -> u64 {
+    self.base.id
+  }
+  fn get_transform(&mut self) -> Option<&mut Transform> {
+    Some(&mut self.transform)
+  }
+  fn get_scripts(&mut self) -> &mut ScriptManager {
     self.base.get_scripts()
   }
-  fn load_scripts(&mut self) {
-    let tmp = self.get_kind().to_string();
-    self.base.load_scripts(&tmp);
+  fn get_base(&mut self) -> &mut Node {
+    &mut self.base
   }
   fn render(&mut self) {
     self.base.render();
@@ -32,29 +150,52 @@ impl NodeLike for Camera {
   fn setup(&mut self) {
     self.base.setup();
   }
+  fn teardown(&mut self) {
+    self.base.teardown();
+  }
   fn update(&mut self, deltatime: f32) {
     self.base.update(deltatime);
   }
-}
-
-impl Luable for Camera {
-  fn as_lua(&self, lua: &mlua::Lua) -> Result<mlua::Value, Box<dyn std::error::Error>> {
-    let table = lua.create_table()?;
-
-    table.set("base", self.base.as_lua(lua)?)?;
-    table.set("focal_length", self.focal_length)?;
-    table.set("transform", self.transform.as_lua(lua)?)?;
-
-    Node::add_kind_to_lua(self.get_kind().to_string(), &table, lua)?;
-
-    Ok(Value::Table(table))
+  fn lua_keys(&self) -> Vec<&'static str> {
+    vec!["zoom", "focal_length", "transform", "target", "smoothing", "deadzone", "limit_min", "limit_max", "trauma", "trauma_decay", "shake_offset", "shake_rotation"]
+  }
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    match key {
+      "zoom" => self.zoom.into_lua(lua),
+      "focal_length" => (1.0 / self.zoom).into_lua(lua),
+      "transform" => Place::field(this, |cam: &mut Camera| &mut cam.transform).into_lua(lua),
+      "target" => self.target().into_lua(lua),
+      "smoothing" => self.smoothing.into_lua(lua),
+      "deadzone" => Place::field(this, |cam: &mut Camera| &mut cam.deadzone).into_lua(lua),
+      "limit_min" => self.limit_min.into_lua(lua),
+      "limit_max" => self.limit_max.into_lua(lua),
+      "trauma" => self.trauma.into_lua(lua),
+      "trauma_decay" => self.trauma_decay.into_lua(lua),
+      "shake_offset" => Place::field(this, |cam: &mut Camera| &mut cam.shake_offset).into_lua(lua),
+      "shake_rotation" => self.shake_rotation.into_lua(lua),
+      "add_trauma" => method(lua, "Camera.add_trauma", |_, (this, amount): (NodeHandle, f32)| {
+        this.cast(|cam: &mut Camera| cam.add_trauma(amount)).ok_or_else(|| mlua::Error::RuntimeError("Node is not a Camera".to_string()))
+      }),
+      _ => self.base.lua_get_common(lua, this, self.get_kind(), key)
+    }
   }
-  fn from_lua(&mut self, value: mlua::Value) -> Result<(), Box<dyn std::error::Error>> {
-    let table = value.as_table().ok_or("Invalid Lua Value".to_string())?;
-    self.base.from_lua(table.get("base")?)?;
-    self.transform.from_lua(table.get("transform")?)?;
-    self.focal_length = table.get("focal_length")?;
-    Ok(())
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "zoom" => self.zoom = f32::from_lua(value, lua)?.max(MIN_ZOOM),
+      "focal_length" => self.zoom = Camera::zoom_for(f32::from_lua(value, lua)?),
+      "transform" => self.transform.replace(Transform::from_lua(value, lua)?),
+      "target" => self.target = Option::<NodeHandle>::from_lua(value, lua)?.map(|target| target.downgrade()),
+      "smoothing" => self.smoothing = f32::from_lua(value, lua)?,
+      "deadzone" => self.deadzone = Vec2::from_lua(value, lua)?,
+      "limit_min" => self.limit_min = Option::<Vec2>::from_lua(value, lua)?,
+      "limit_max" => self.limit_max = Option::<Vec2>::from_lua(value, lua)?,
+      "trauma" => self.trauma = f32::from_lua(value, lua)?.clamp(0.0, 1.0),
+      "trauma_decay" => self.trauma_decay = f32::from_lua(value, lua)?,
+      "shake_offset" => self.shake_offset = Vec2::from_lua(value, lua)?,
+      "shake_rotation" => self.shake_rotation = f32::from_lua(value, lua)?,
+      _ => return Ok(false)
+    }
+    Ok(true)
   }
 }
 
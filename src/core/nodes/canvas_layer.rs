@@ -0,0 +1,71 @@
+use mlua::{FromLua, IntoLua, Lua, Value};
+
+use crate::core::{core::Downcastable, nodelike::NodeLike, nodes::node::Node, proxy::NodeHandle, script_manager::ScriptManager, transform::ParentFrame};
+
+
+//? Everything below a canvas layer is placed in screen pixels and ignores the camera, which is what HUDs and menus want.
+pub struct CanvasLayer {
+  base: Node,
+  pub order: i32,
+  pub visible: bool,
+}
+
+impl CanvasLayer {
+  pub fn new(order: i32) -> CanvasLayer {
+    CanvasLayer { base: Node::new(), order, visible: true }
+  }
+}
+
+impl NodeLike for CanvasLayer {
+  fn get_kind(&self) -> &str {
+    "CanvasLayer"
+  }
+  fn get_id(&self) -> u64 {
+    self.base.id
+  }
+  fn get_scripts(&mut self) -> &mut ScriptManager {
+    self.base.get_scripts()
+  }
+  fn get_base(&mut self) -> &mut Node {
+    &mut self.base
+  }
+  fn propagate(&mut self, _: ParentFrame) -> ParentFrame {
+    ParentFrame::SCREEN
+  }
+  fn render(&mut self) {
+    self.base.render();
+  }
+  fn setup(&mut self) {
+    self.base.setup();
+  }
+  fn teardown(&mut self) {
+    self.base.teardown();
+  }
+  fn update(&mut self, deltatime: f32) {
+    self.base.update(deltatime);
+  }
+  fn lua_keys(&self) -> Vec<&'static str> {
+    vec!["order", "visible"]
+  }
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    match key {
+      "order" => self.order.into_lua(lua),
+      "visible" => self.visible.into_lua(lua),
+      _ => self.base.lua_get_common(lua, this, self.get_kind(), key)
+    }
+  }
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "order" => self.order = i32::from_lua(value, lua)?,
+      "visible" => self.visible = bool::from_lua(value, lua)?,
+      _ => return Ok(false)
+    }
+    Ok(true)
+  }
+}
+
+impl Downcastable for CanvasLayer {
+  fn as_any(&mut self) -> &mut dyn std::any::Any {
+    self
+  }
+}
@@ -0,0 +1,308 @@
+use std::f32::consts::PI;
+
+use mlua::{FromLua, IntoLua, Lua, Value};
+
+use crate::core::{broadphase::Aabb, collision_layers::LayerMask, core::Downcastable, nodelike::NodeLike, nodes::{collider::Collider, node::Node}, physics::{owning_body, separation}, proxy::{NodeHandle, Place, method}, script_manager::ScriptManager, shape::Hull, transform::Transform, vec2::Vec2};
+
+const MAX_SLIDES: usize = 4;
+//? Long moves are split into steps of at most half the smallest collider, so fast characters cannot pass through thin walls.
+const MAX_STEPS: usize = 64;
+
+struct SlideShape {
+  layer: LayerMask,
+  mask: LayerMask,
+  hull: Hull,
+  one_way: Option<Vec2>,
+}
+
+impl SlideShape {
+  fn of(collider: &NodeHandle) -> Option<SlideShape> {
+    collider.global_frame();
+    collider.cast(|coll: &mut Collider| SlideShape { layer: coll.layer, mask: coll.mask, hull: coll.hull(), one_way: coll.one_way_up() })
+  }
+}
+
+fn deepest(shapes: &[SlideShape], obstacles: &[SlideShape], offset: Vec2, travel: Vec2) -> Option<(Vec2, f32)> {
+  let mut best: Option<(Vec2, f32)> = None;
+  for shape in shapes {
+    let hull: Hull = shape.hull.translated(offset);
+    for obstacle in obstacles.iter().filter(|obstacle| shape.mask.intersects(obstacle.layer)) {
+      if let Some((normal, depth)) = separation(&obstacle.hull, obstacle.one_way, &hull, None, travel)
+        && best.is_none_or(|(_, best)| depth > best) {
+        best = Some((normal, depth));
+      }
+    }
+  }
+  best
+}
+
+pub struct CharacterBody {
+  base: Node,
+  pub transform: Transform,
+  pub velocity: Vec2,
+  pub up_direction: Vec2,
+  pub floor_max_angle: f32,
+  pub floor_snap: f32,
+  on_floor: bool,
+  on_wall: bool,
+  on_ceiling: bool,
+  floor_normal: Vec2,
+}
+
+impl CharacterBody {
+  pub fn new(pos: Vec2) -> CharacterBody {
+    CharacterBody {
+      base: Node::new(),
+      transform: Transform::new(pos, Vec2::ZERO),
+      velocity: Vec2::ZERO,
+      up_direction: Vec2::new(0.0, -1.0),
+      floor_max_angle: PI / 4.0,
+      floor_snap: 4.0,
+      on_floor: false,
+      on_wall: false,
+      on_ceiling: false,
+      floor_normal: Vec2::ZERO,
+    }
+  }
+
+  pub fn move_and_slide(this: &NodeHandle, deltatime: f32) -> Option<bool> {
+    this.cast(|_: &mut CharacterBody| ())?;
+    let own: Vec<NodeHandle> = this.descendants().into_iter().skip(1)
+      .filter(|node| node.cast(|_: &mut Collider| ()).is_some())
+      .filter(|node| owning_body(node).is_some_and(|owner| owner.same(this)))
+      .collect();
+    let shapes: Vec<SlideShape> = own.iter().filter_map(SlideShape::of).collect();
+    let Some(bounds) = shapes.iter().map(|shape| shape.hull.aabb()).reduce(Aabb::union) else {
+      this.global_frame();
+      let collided: Option<bool> = this.cast(|character: &mut CharacterBody| character.slide(&[], &[], deltatime));
+      Collider::moved(this);
+      return collided
+    };
+    let (motion, snap): (Vec2, f32) = this.cast(|character: &mut CharacterBody| (character.velocity * deltatime, character.floor_snap))?;
+    let swept: Aabb = bounds.union(Aabb { min: bounds.min + motion, max: bounds.max + motion }).grown(snap.max(0.0));
+    let obstacles: Vec<SlideShape> = Collider::candidates(swept).iter()
+      .filter(|coll| !own.iter().any(|own| own.same(coll)))
+      .filter_map(SlideShape::of)
+      .collect();
+    this.global_frame();
+    let collided: Option<bool> = this.cast(|character: &mut CharacterBody| character.slide(&shapes, &obstacles, deltatime));
+    Collider::moved(this);
+    collided
+  }
+
+  fn slide(&mut self, shapes: &[SlideShape], obstacles: &[SlideShape], deltatime: f32) -> bool {
+    let was_on_floor: bool = self.on_floor;
+    (self.on_floor, self.on_wall, self.on_ceiling, self.floor_normal) = (false, false, false, Vec2::ZERO);
+    let up: Vec2 = self.up_direction.normalized();
+    let floor_cos: f32 = self.floor_max_angle.cos();
+
+    let smallest: f32 = shapes.iter().map(|shape| shape.hull.thickness()).fold(f32::INFINITY, f32::min);
+    let max_step: f32 = (smallest / 2.0).max(1.0);
+    let steps: usize = (((self.velocity * deltatime).length() / max_step).ceil() as usize).clamp(1, MAX_STEPS);
+
+    let mut offset: Vec2 = Vec2::ZERO;
+    let mut collided: bool = false;
+    for _ in 0..steps {
+      let travel: Vec2 = self.velocity * deltatime / steps as f32;
+      offset += travel;
+      for _ in 0..MAX_SLIDES {
+        let Some((normal, depth)) = deepest(shapes, obstacles, offset, travel) else {
+          break
+        };
+        collided = true;
+        let along_up: f32 = normal.dot(up);
+        //? Floors push straight up, so standing on a slope does not slide down it and walking into one climbs it.
+        if along_up >= floor_cos {
+          (self.on_floor, self.floor_normal) = (true, normal);
+          offset += up * (depth / along_up);
+          let falling: f32 = self.velocity.dot(up);
+          if falling < 0.0 {
+            self.velocity -= up * falling;
+          }
+        } else {
+          if along_up <= -floor_cos {
+            self.on_ceiling = true;
+          } else {
+            self.on_wall = true;
+          }
+          offset += normal * depth;
+          let into: f32 = self.velocity.dot(normal);
+          if into < 0.0 {
+            self.velocity -= normal * into;
+          }
+        }
+      }
+    }
+
+    if was_on_floor && !self.on_floor && self.floor_snap > 0.0 && self.velocity.dot(up) <= 0.0 {
+      let snap: Vec2 = -up * self.floor_snap;
+      if let Some((normal, depth)) = deepest(shapes, obstacles, offset + snap, snap) {
+        let along_up: f32 = normal.dot(up);
+        if along_up >= floor_cos {
+          (self.on_floor, self.floor_normal) = (true, normal);
+          offset += snap + up * (depth / along_up);
+        }
+      }
+    }
+
+    let global: Vec2 = self.transform.global_pos() + offset;
+    self.transform.pos = self.transform.parent.to_local(global);
+    collided
+  }
+}
+
+impl NodeLike for CharacterBody {
+  fn get_kind(&self) -> &str {
+    "CharacterBody"
+  }
+  fn get_id(&self) -> u64 {
+    self.base.id
+  }
+  fn get_scripts(&mut self) -> &mut ScriptManager {
+    self.base.get_scripts()
+  }
+  fn get_base(&mut self) -> &mut Node {
+    &mut self.base
+  }
+  fn get_transform(&mut self) -> Option<&mut Transform> {
+    Some(&mut self.transform)
+  }
+  fn render(&mut self) {
+    self.base.render();
+  }
+  fn setup(&mut self) {
+    self.base.setup();
+  }
+  fn teardown(&mut self) {
+    self.base.teardown();
+  }
+  fn update(&mut self, deltatime: f32) {
+    self.base.update(deltatime);
+  }
+  fn lua_keys(&self) -> Vec<&'static str> {
+    vec!["transform", "velocity", "up_direction", "floor_max_angle", "floor_snap", "on_floor", "on_wall", "on_ceiling", "floor_normal"]
+  }
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    match key {
+      "transform" => Place::field(this, |character: &mut CharacterBody| &mut character.transform).into_lua(lua),
+      "velocity" => Place::field(this, |character: &mut CharacterBody| &mut character.velocity).into_lua(lua),
+      "up_direction" => Place::field(this, |character: &mut CharacterBody| &mut character.up_direction).into_lua(lua),
+      "floor_max_angle" => self.floor_max_angle.into_lua(lua),
+      "floor_snap" => self.floor_snap.into_lua(lua),
+      "on_floor" => self.on_floor.into_lua(lua),
+      "on_wall" => self.on_wall.into_lua(lua),
+      "on_ceiling" => self.on_ceiling.into_lua(lua),
+      "floor_normal" => self.floor_normal.into_lua(lua),
+      "move_and_slide" => method(lua, "CharacterBody.move_and_slide", |_, (this, deltatime): (NodeHandle, f32)| {
+        CharacterBody::move_and_slide(&this, deltatime).ok_or_else(|| mlua::Error::RuntimeError("Node is not a CharacterBody".to_string()))
+      }),
+      _ => self.base.lua_get_common(lua, this, self.get_kind(), key)
+    }
+  }
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "transform" => self.transform.replace(Transform::from_lua(value, lua)?),
+      "velocity" => self.velocity = Vec2::from_lua(value, lua)?,
+      "up_direction" => self.up_direction = Vec2::from_lua(value, lua)?,
+      "floor_max_angle" => self.floor_max_angle = f32::from_lua(value, lua)?,
+      "floor_snap" => self.floor_snap = f32::from_lua(value, lua)?,
+      _ => return Ok(false)
+    }
+    Ok(true)
+  }
+}
+
+impl Downcastable for CharacterBody {
+  fn as_any(&mut self) -> &mut dyn std::any::Any {
+    self
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::core::testing::Headless;
+
+  struct Moved {
+    pos: Vec2,
+    on_floor: bool,
+    on_wall: bool,
+    on_ceiling: bool,
+    floor_normal: Vec2,
+  }
+
+  fn slide(engine: &Headless, name: &str, velocity: Vec2, deltatime: f32, steps: usize) -> Moved {
+    let character: NodeHandle = engine.get_node(name).expect("No such character");
+    for _ in 0..steps {
+      character.cast(|character: &mut CharacterBody| character.velocity = velocity);
+      CharacterBody::move_and_slide(&character, deltatime);
+    }
+    character.cast(|character: &mut CharacterBody| Moved {
+      pos: character.transform.pos,
+      on_floor: character.on_floor,
+      on_wall: character.on_wall,
+      on_ceiling: character.on_ceiling,
+      floor_normal: character.floor_normal,
+    }).expect("Not a CharacterBody")
+  }
+
+  fn assert_near(actual: Vec2, expected: Vec2) {
+    assert!((actual - expected).length() < 1e-3, "{:?} is not {:?}", actual, expected);
+  }
+
+  fn characters() -> Headless {
+    let mut engine: Headless = Headless::load("characters.lua");
+    engine.run_frames(1, 1.0 / 60.0);
+    engine
+  }
+
+  #[test]
+  fn falling_characters_land_and_leave_ledges() {
+    let engine: Headless = characters();
+    let landed: Moved = slide(&engine, "faller", Vec2::new(0.0, 300.0), 0.1, 3);
+    assert!(landed.on_floor && !landed.on_wall);
+    assert_near(landed.pos, Vec2::new(50.0, 90.0));
+    assert_near(landed.floor_normal, Vec2::new(0.0, -1.0));
+
+    let walking: Moved = slide(&engine, "faller", Vec2::new(400.0, 0.0), 0.25, 1);
+    assert!(walking.on_floor);
+    assert_near(walking.pos, Vec2::new(150.0, 90.0));
+
+    let off: Moved = slide(&engine, "faller", Vec2::new(400.0, 0.0), 0.25, 1);
+    assert!(!off.on_floor);
+    assert_near(off.pos, Vec2::new(250.0, 90.0));
+  }
+
+  #[test]
+  fn walls_stop_characters_walking_into_them() {
+    let engine: Headless = characters();
+    let blocked: Moved = slide(&engine, "walker", Vec2::new(300.0, 0.0), 0.1, 1);
+    assert!(blocked.on_wall && !blocked.on_floor && !blocked.on_ceiling);
+    assert_near(blocked.pos, Vec2::new(1190.0, 85.0));
+  }
+
+  #[test]
+  fn slopes_count_as_floors_and_do_not_slide() {
+    let engine: Headless = characters();
+    let standing: Moved = slide(&engine, "climber", Vec2::new(0.0, 300.0), 0.1, 4);
+    assert!(standing.on_floor && !standing.on_wall);
+    assert_near(standing.floor_normal, Vec2::new(-(0.3f32.sin()), -(0.3f32.cos())));
+    assert_eq!(standing.pos.get_x(), 2100.0);
+    let resting: f32 = standing.pos.get_y();
+    let still: Moved = slide(&engine, "climber", Vec2::new(0.0, 300.0), 0.1, 4);
+    assert!(still.on_floor);
+    assert_near(still.pos, Vec2::new(2100.0, resting));
+  }
+
+  #[test]
+  fn one_way_platforms_only_stop_characters_from_above() {
+    let engine: Headless = characters();
+    let jumped: Moved = slide(&engine, "jumper", Vec2::new(0.0, -300.0), 0.1, 1);
+    assert!(!jumped.on_ceiling && !jumped.on_floor);
+    assert_near(jumped.pos, Vec2::new(3050.0, 85.0));
+
+    let landed: Moved = slide(&engine, "jumper", Vec2::new(0.0, 100.0), 0.1, 1);
+    assert!(landed.on_floor);
+    assert_near(landed.pos, Vec2::new(3050.0, 90.0));
+  }
+}
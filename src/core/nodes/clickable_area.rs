@@ -1,9 +1,6 @@
-use std::sync::Arc;
+use mlua::{FromLua, IntoLua, Lua, Value};
 
-use macroquad::{input::mouse_position, math::Rect};
-use mlua::Value;
-
-use crate::core::{core::{Downcastable, Luable}, engine::{MAIN_CAMERA, main_camera}, nodelike::NodeLike, nodes::node::Node, script_manager::ScriptManager, transform::Transform, vec2::Vec2};
+use crate::core::{core::Downcastable, engine::{is_headless, screen_to_world}, nodelike::NodeLike, nodes::node::Node, proxy::{NodeHandle, Place, method}, script_manager::ScriptManager, stretch::virtual_mouse, transform::Transform, vec2::Vec2};
 
 
 pub struct ClickableArea {
@@ -15,21 +12,42 @@ impl ClickableArea {
   pub fn new(pos: Vec2, size: Vec2) -> Self {
     ClickableArea { base: Node::new(), transform: Transform::new(pos, size) }
   }
+
+  pub fn clicked(&self, button: i64) -> bool {
+    if is_headless() {
+      return false
+    }
+    let screen: Vec2 = virtual_mouse();
+    let mouse: Vec2 = if self.transform.parent.screen { screen } else { screen_to_world(screen) };
+    let inside = self.transform.contains(mouse);
+    let pressed = match button {
+        0 => macroquad::input::is_mouse_button_pressed(macroquad::input::MouseButton::Left),
+        1 => macroquad::input::is_mouse_button_pressed(macroquad::input::MouseButton::Right),
+        2 => macroquad::input::is_mouse_button_pressed(macroquad::input::MouseButton::Middle),
+        _ => false,
+    };
+    inside && pressed
+  }
 }
 
 impl NodeLike for ClickableArea {
   fn setup(&mut self) {
     self.base.setup();
   }
+  fn teardown(&mut self) {
+    self.base.teardown();
+  }
   fn update(&mut self, deltatime: f32) {
     self.base.update(deltatime);
   }
   fn get_scripts(&mut self) -> &mut ScriptManager {
     self.base.get_scripts()
   }
-  fn load_scripts(&mut self) {
-    let tmp = self.get_kind().to_string();
-    self.base.load_scripts(&tmp);
+  fn get_base(&mut self) -> &mut Node {
+    &mut self.base
+  }
+  fn get_transform(&mut self) -> Option<&mut Transform> {
+    Some(&mut self.transform)
   }
   fn render(&mut self) {
     self.base.render();
@@ -37,47 +55,39 @@ impl NodeLike for ClickableArea {
   fn get_kind(&self) -> &str {
     "ClickableArea"
   }
-}
-
-impl Luable for ClickableArea {
-  fn as_lua(&self, lua: &mlua::Lua) -> Result<mlua::Value, Box<dyn std::error::Error>> {
-    let tbl = lua.create_table()?;
-    let base = self.base.as_lua(lua)?;
-    let transform = self.transform.as_lua(lua)?;
-
-    tbl.set("base", base)?;
-    tbl.set("transform", transform)?;
-    
-    let temp = {
-      let (actual_position, actual_size) = self.transform.get_camera_relative();
-      Transform::new(actual_position, actual_size)
-    };
-    tbl.set("clicked", lua.create_function(move |_, s: i64| {
-      let (x, y) = mouse_position();
-      let mouse = Vec2::new(x as i32, y as i32);
-      let inside = temp.contains(mouse);
-      let pressed = match s {
-          0 => macroquad::input::is_mouse_button_pressed(macroquad::input::MouseButton::Left),
-          1 => macroquad::input::is_mouse_button_pressed(macroquad::input::MouseButton::Right),
-          2 => macroquad::input::is_mouse_button_pressed(macroquad::input::MouseButton::Middle),
-          _ => false,
-      };
-      Ok(inside && pressed)
-    })?)?;
-    Node::add_kind_to_lua(self.get_kind().to_string(), &tbl, lua)?;
-    Ok(Value::Table(tbl))
+  fn get_id(&self) -> u64 {
+    self.base.id
   }
-
-  fn from_lua(&mut self, value: Value) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(table) = value.as_table() {
-      let base: Value = table.get("base")?;
-      self.base.from_lua(base)?;
-      let transform: Value = table.get("transform")?;
-      self.transform.from_lua(transform)?;
-      return Ok(())
+  fn lua_keys(&self) -> Vec<&'static str> {
+    vec!["transform"]
+  }
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    match key {
+      "transform" => Place::field(this, |area: &mut ClickableArea| &mut area.transform).into_lua(lua),
+      //? Built per read rather than cached, so the older 'area.clicked(button)' keeps working next to 'area:clicked(button)'.
+      "clicked" => {
+        let area: NodeHandle = this.clone();
+        lua.create_function(move |lua, (first, second): (Value, Option<i64>)| {
+          let button: i64 = match first {
+            Value::Integer(_) | Value::Number(_) => i64::from_lua(first, lua)?,
+            _ => second.ok_or_else(|| mlua::Error::RuntimeError("ClickableArea.clicked expects a mouse button".to_string()))?
+          };
+          area.cast(|area: &mut ClickableArea| area.clicked(button)).ok_or_else(|| mlua::Error::RuntimeError("Node is not a ClickableArea".to_string()))
+        })?.into_lua(lua)
+      },
+      "contains" => method(lua, "ClickableArea.contains", |_, (this, point): (NodeHandle, Vec2)| {
+        this.global_frame();
+        this.cast(|area: &mut ClickableArea| area.transform.contains(point)).ok_or_else(|| mlua::Error::RuntimeError("Node is not a ClickableArea".to_string()))
+      }),
+      _ => self.base.lua_get_common(lua, this, self.get_kind(), key)
     }
-
-    Err("Invalid Lua Value".into())
+  }
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "transform" => self.transform.replace(Transform::from_lua(value, lua)?),
+      _ => return Ok(false)
+    }
+    Ok(true)
   }
 }
 
@@ -1,28 +1,166 @@
+use std::{collections::{BTreeMap, HashSet}, sync::Mutex};
 
-use std::sync::{Arc, Mutex};
-
-use mlua::{Function, Table, Value};
+use mlua::{FromLua, IntoLua, Lua, Value};
 use once_cell::sync::Lazy;
 
-use crate::core::{core::{Downcastable, Luable}, nodelike::NodeLike, nodes::node::Node, transform::Transform, vec2::Vec2};
+use crate::core::{broadphase::{Aabb, CELL_SIZE, SpatialHash}, collision_layers::LayerMask, core::Downcastable, nodelike::NodeLike, nodes::node::Node, physics::owning_body, proxy::{NodeHandle, Place, WeakNodeHandle, method}, query::{self, Hit}, script_manager::ScriptManager, shape::{Hull, Shape}, transform::Transform, vec2::Vec2};
+
+struct Registered {
+  id: u64,
+  scene: u64,
+  node: NodeHandle,
+}
+
+//? Scenes frozen under a pushed one keep their colliders, but only the active scene's are looked at.
+struct ColliderManager {
+  colliders: Vec<Registered>,
+  scene: u64,
+  indexed: Vec<(u64, NodeHandle)>,
+  hash: SpatialHash,
+  alive: HashSet<u64>,
+  //? Active colliders the hash may not find: registered, or moved, since the last refresh.
+  fresh: BTreeMap<u64, NodeHandle>,
+  //? Nodes written to since then. Their subtrees are only walked by the next query, when no node is locked.
+  moved: Vec<WeakNodeHandle>,
+}
 
-static COLLIDER_MANAGER: Lazy<Mutex<Vec<Arc<Collider>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+static COLLIDER_MANAGER: Lazy<Mutex<ColliderManager>> = Lazy::new(|| Mutex::new(ColliderManager {
+  colliders: Vec::new(),
+  scene: 0,
+  indexed: Vec::new(),
+  hash: SpatialHash::new(CELL_SIZE),
+  alive: HashSet::new(),
+  fresh: BTreeMap::new(),
+  moved: Vec::new(),
+}));
 
 pub struct Collider {
   base: Node,
-  transform: Transform,
-  layer: String,
+  pub transform: Transform,
+  pub layer: LayerMask,
+  pub mask: LayerMask,
+  //? Only stops what comes from above, along the collider's own up, so bodies can jump through from below.
+  pub one_way: bool,
+  pub shape: Shape,
 }
 
 impl Collider {
-  pub fn new(pos: Vec2, size: Vec2, layer: String) -> Arc<Collider> {
-    let ret = Arc::new(Collider { base: Node::new(), transform: Transform::new(pos, size), layer: layer });
-    COLLIDER_MANAGER.lock().unwrap().push(ret.clone());
-    ret
+  pub fn new(pos: Vec2, size: Vec2, layer: LayerMask, mask: LayerMask) -> Collider {
+    Collider { base: Node::new(), transform: Transform::new(pos, size), layer, mask, one_way: false, shape: Shape::Rect }
+  }
+
+  pub fn register(collider: NodeHandle, scene: u64) {
+    let id: u64 = collider.id();
+    let mut manager = COLLIDER_MANAGER.lock().unwrap();
+    if manager.colliders.iter().any(|coll| coll.id == id) {
+      return
+    }
+    if scene == manager.scene {
+      manager.alive.insert(id);
+      manager.fresh.insert(id, collider.clone());
+    }
+    manager.colliders.push(Registered { id, scene, node: collider });
+  }
+
+  pub fn registered() -> Vec<NodeHandle> {
+    let manager = COLLIDER_MANAGER.lock().unwrap();
+    manager.colliders.iter().filter(|coll| coll.scene == manager.scene).map(|coll| coll.node.clone()).collect()
+  }
+
+  //? May be called while 'node' is locked, so nothing here looks inside it.
+  pub fn moved(node: &NodeHandle) {
+    let mut manager = COLLIDER_MANAGER.lock().unwrap();
+    if !manager.alive.is_empty() {
+      manager.moved.push(node.downgrade());
+    }
+  }
+
+  fn settle_moves() {
+    let moved: Vec<WeakNodeHandle> = std::mem::take(&mut COLLIDER_MANAGER.lock().unwrap().moved);
+    let colliders: Vec<(u64, NodeHandle)> = moved.iter()
+      .filter_map(WeakNodeHandle::upgrade)
+      .flat_map(|node| node.descendants())
+      .filter(|node| node.cast(|_: &mut Collider| ()).is_some())
+      .map(|node| (node.id(), node))
+      .collect();
+    let mut manager = COLLIDER_MANAGER.lock().unwrap();
+    for (id, collider) in colliders {
+      if manager.alive.contains(&id) {
+        manager.fresh.insert(id, collider);
+      }
+    }
+  }
+
+  pub fn refresh_broadphase(scene: &NodeHandle) {
+    COLLIDER_MANAGER.lock().unwrap().scene = scene.id();
+    let colliders: Vec<NodeHandle> = Collider::registered();
+    let (indexed, bounds): (Vec<(u64, NodeHandle)>, Vec<Aabb>) = colliders.into_iter().filter_map(|coll| {
+      coll.global_frame();
+      let bounds: Aabb = coll.cast(|coll: &mut Collider| coll.hull().aabb())?;
+      Some(((coll.id(), coll), bounds))
+    }).unzip();
+    let mut manager = COLLIDER_MANAGER.lock().unwrap();
+    manager.hash = SpatialHash::build(bounds, CELL_SIZE);
+    manager.indexed = indexed;
+    manager.alive = manager.colliders.iter().filter(|coll| coll.scene == manager.scene).map(|coll| coll.id).collect();
+    manager.fresh.clear();
+    manager.moved.clear();
+  }
+
+  //? Must not be called while any node is locked.
+  pub fn candidates(bounds: Aabb) -> Vec<NodeHandle> {
+    Collider::settle_moves();
+    let manager = COLLIDER_MANAGER.lock().unwrap();
+    let near = manager.hash.query(bounds).into_iter()
+      .map(|index| &manager.indexed[index])
+      .filter(|(id, _)| manager.alive.contains(id) && !manager.fresh.contains_key(id))
+      .map(|(_, coll)| coll.clone());
+    near.chain(manager.fresh.values().cloned()).collect()
   }
 
-  pub fn empty() -> Collider {
-    Collider { base: Node::new(), transform: Transform::new(Vec2::ZERO, Vec2::ZERO), layer: String::new() }
+  pub fn hull(&self) -> Hull {
+    self.shape.hull(&self.transform)
+  }
+
+  pub fn one_way_up(&self) -> Option<Vec2> {
+    self.one_way.then(|| Vec2::new(0.0, -1.0).rotated(self.transform.global_rotation()))
+  }
+
+  //? Called from teardown while the node is locked, so only the stored ids are looked at.
+  pub fn unregister(id: u64) {
+    let mut manager = COLLIDER_MANAGER.lock().unwrap();
+    manager.colliders.retain(|coll| coll.id != id);
+    manager.alive.remove(&id);
+    manager.fresh.remove(&id);
+  }
+
+  pub fn collides(this: &NodeHandle, force_all: bool) -> Option<bool> {
+    this.global_frame();
+    let (id, mask, hull): (u64, LayerMask, Hull) = this.cast(|coll: &mut Collider| (coll.base.id, coll.mask, coll.hull()))?;
+    let colliders: Vec<NodeHandle> = if force_all { Collider::registered() } else { Collider::candidates(hull.aabb()) };
+    let others: Vec<Hull> = colliders.iter()
+      .filter(|other| other.id() != id)
+      .filter_map(|other| {
+        other.global_frame();
+        other.cast(|coll: &mut Collider| (coll.layer, coll.hull()))
+      })
+      .filter(|(other_layer, _)| mask.intersects(*other_layer))
+      .map(|(_, other)| other)
+      .collect();
+    let flag = if force_all {
+      others.iter().all(|ele| hull.overlaps(ele))
+    } else {
+      others.iter().any(|ele| hull.overlaps(ele))
+    };
+    Some(flag)
+  }
+
+  pub fn sweep(this: &NodeHandle, motion: Vec2) -> Option<Option<Hit>> {
+    this.global_frame();
+    let (mask, hull): (LayerMask, Hull) = this.cast(|coll: &mut Collider| (coll.mask, coll.hull()))?;
+    let body: Option<NodeHandle> = owning_body(this);
+    let own = |other: &NodeHandle| other.same(this) || body.as_ref().is_some_and(|body| owning_body(other).is_some_and(|owner| owner.same(body)));
+    Some(query::sweep(&hull, motion, mask, own))
   }
 }
 
@@ -30,12 +168,17 @@ impl NodeLike for Collider {
   fn get_kind(&self) -> &str {
     "Collider"
   }
-  fn get_scripts(&mut self) -> &mut crate::core::script_manager::ScriptManager {
+  fn get_id(&self) -> u64 {
+    self.base.id
+  }
+  fn get_scripts(&mut self) -> &mut ScriptManager {
     self.base.get_scripts()
   }
-  fn load_scripts(&mut self) {
-    let tmp = self.get_kind().to_string();
-    self.base.load_scripts(&tmp);
+  fn get_base(&mut self) -> &mut Node {
+    &mut self.base
+  }
+  fn get_transform(&mut self) -> Option<&mut Transform> {
+    Some(&mut self.transform)
   }
   fn render(&mut self) {
     self.base.render();
@@ -43,9 +186,43 @@ impl NodeLike for Collider {
   fn setup(&mut self) {
     self.base.setup();
   }
+  fn teardown(&mut self) {
+    Collider::unregister(self.base.id);
+    self.base.teardown();
+  }
   fn update(&mut self, deltatime: f32) {
     self.base.update(deltatime);
   }
+  fn lua_keys(&self) -> Vec<&'static str> {
+    vec!["transform", "layer", "mask", "one_way", "shape"]
+  }
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    match key {
+      "transform" => Place::field(this, |coll: &mut Collider| &mut coll.transform).into_lua(lua),
+      "layer" => self.layer.into_lua(lua),
+      "mask" => self.mask.into_lua(lua),
+      "one_way" => self.one_way.into_lua(lua),
+      "shape" => self.shape.clone().into_lua(lua),
+      "collides" => method(lua, "Collider.collides", |_, (this, force_all): (NodeHandle, Option<bool>)| {
+        Collider::collides(&this, force_all.unwrap_or(false)).ok_or_else(|| mlua::Error::RuntimeError("Node is not a Collider".to_string()))
+      }),
+      "sweep" => method(lua, "Collider.sweep", |_, (this, motion): (NodeHandle, Vec2)| {
+        Collider::sweep(&this, motion).ok_or_else(|| mlua::Error::RuntimeError("Node is not a Collider".to_string()))
+      }),
+      _ => self.base.lua_get_common(lua, this, self.get_kind(), key)
+    }
+  }
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "transform" => self.transform.replace(Transform::from_lua(value, lua)?),
+      "layer" => self.layer = LayerMask::from_lua(value, lua)?,
+      "mask" => self.mask = LayerMask::from_lua(value, lua)?,
+      "one_way" => self.one_way = bool::from_lua(value, lua)?,
+      "shape" => self.shape = Shape::from_lua(value, lua)?,
+      _ => return Ok(false)
+    }
+    Ok(true)
+  }
 }
 
 impl Downcastable for Collider {
@@ -54,35 +231,24 @@ impl Downcastable for Collider {
   }
 }
 
-impl Luable for Collider {
-  fn as_lua(&self, lua: &mlua::Lua) -> Result<mlua::Value, Box<dyn std::error::Error>> {
-    let table = lua.create_table()?;
-    table.set("base", self.base.as_lua(lua)?)?;
-    table.set("transform", self.transform.as_lua(lua)?)?;
-    table.set("layer", self.layer.clone())?;
-    table.set("collides", lua.create_function(|_, (this, force_all): (Table, Option<bool>)| {
-      let mut transform: Transform = Transform::new(Vec2::ZERO, Vec2::ZERO);
-      transform.from_lua(this.get("transform")?).expect("Invalid Lua Value");
-      let layer: String = this.get("layer")?;
-      let id: u64 = this.get::<Table>("base")?.get::<Function>("id")?.call::<u64>(())?;
-      let manager = COLLIDER_MANAGER.lock().unwrap();
-      let mut to_check = manager.iter().filter(|coll| coll.base.id != id && coll.layer == layer);
-      let flag = if force_all.is_some() && force_all.unwrap() {
-        to_check.all(|ele| transform.instersects(&ele.transform))
-      } else {
-        to_check.any(|ele| transform.instersects(&ele.transform))
-      };
-      Ok(flag)
-    })?)?;
-    Node::add_kind_to_lua(self.get_kind().to_string(), &table, lua)?;
-    Ok(Value::Table(table))
-  }
-
-  fn from_lua(&mut self, value: Value) -> Result<(), Box<dyn std::error::Error>> {
-    let table: &Table = value.as_table().ok_or("Invalid Lua Value")?;
-    self.base.from_lua(table.get("base")?)?;
-    self.transform.from_lua(table.get("transform")?)?;
-    self.layer = table.get("layer")?;
-    Ok(())
+#[cfg(test)]
+mod tests {
+  use crate::core::testing::Headless;
+
+  #[test]
+  fn colliders_moved_through_places_and_parents_are_found() {
+    let mut engine: Headless = Headless::load("moving_colliders.lua");
+    engine.run_frames(1, 1.0 / 60.0);
+    assert!(!engine.eval::<bool>("return root.mover:collides()"));
+    assert!(engine.eval::<bool>("root.mover.transform.pos = Vec2(195, 0); return root.mover:collides()"));
+    assert_eq!(engine.eval::<usize>("return #query_point(Vec2(202, 5))"), 2);
+    assert!(!engine.eval::<bool>("root.mover.transform.pos.x = 0; return root.mover:collides()"));
+
+    assert_eq!(engine.eval::<usize>("return #query_point(Vec2(205, 5))"), 1);
+    assert!(engine.eval::<bool>("root.rig.transform.pos = Vec2(200, 0); return root.rig.children.arm:collides()"));
+    assert_eq!(engine.eval::<usize>("return #query_point(Vec2(205, 5))"), 2);
+
+    assert!(engine.eval::<bool>("local arm = root.rig.children.arm; root.rig.children.arm = nil; root.mover.children.arm = arm; return arm:collides()"));
+    assert_eq!(engine.eval::<usize>("return #query_point(Vec2(5, 5))"), 2);
   }
 }
@@ -0,0 +1,185 @@
+use std::any::Any;
+
+use macroquad::shapes::{draw_circle, draw_circle_lines, draw_line};
+use mlua::{FromLua, IntoLua, Lua, Value};
+
+use crate::core::{color::Color, core::Downcastable, engine::{render_alpha, world_to_screen}, layering::Layering, nodelike::NodeLike, nodes::node::Node, physics::debug_physics, proxy::{NodeHandle, Place, WeakNodeHandle}, script_manager::ScriptManager, vec2::Vec2};
+
+const SPRING_COILS: usize = 8;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum JointKind {
+  Distance,
+  Pin,
+  Spring,
+  Rope,
+}
+
+impl JointKind {
+  pub fn parse(name: &str) -> mlua::Result<JointKind> {
+    match name {
+      "distance" => Ok(JointKind::Distance),
+      "pin" => Ok(JointKind::Pin),
+      "spring" => Ok(JointKind::Spring),
+      "rope" => Ok(JointKind::Rope),
+      _ => Err(mlua::Error::RuntimeError(format!("Unknown joint kind '{}', expected distance, pin, spring or rope", name)))
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      JointKind::Distance => "distance",
+      JointKind::Pin => "pin",
+      JointKind::Spring => "spring",
+      JointKind::Rope => "rope",
+    }
+  }
+}
+
+//? Bodies are held weakly, so a joint below one of its bodies does not keep it alive.
+pub struct Joint {
+  base: Node,
+  pub layering: Layering,
+  pub kind: JointKind,
+  pub body_a: Option<WeakNodeHandle>,
+  pub body_b: Option<WeakNodeHandle>,
+  pub anchor_a: Vec2,
+  pub anchor_b: Vec2,
+  pub length: Option<f32>,
+  pub stiffness: f32,
+  pub damping: f32,
+  pub color: Color,
+  ends: Option<(Vec2, Vec2)>,
+  prev_ends: Option<(Vec2, Vec2)>,
+}
+
+impl Joint {
+  pub fn new(kind: JointKind, body_a: &NodeHandle, body_b: Option<&NodeHandle>) -> Joint {
+    Joint {
+      base: Node::new(),
+      layering: Layering::new(),
+      kind,
+      body_a: Some(body_a.downgrade()),
+      body_b: body_b.map(|body| body.downgrade()),
+      anchor_a: Vec2::ZERO,
+      anchor_b: Vec2::ZERO,
+      length: None,
+      stiffness: 50.0,
+      damping: 2.0,
+      color: Color::from_rgb(255, 200, 0),
+      ends: None,
+      prev_ends: None,
+    }
+  }
+
+  pub fn set_ends(&mut self, a: Vec2, b: Vec2) {
+    self.prev_ends = self.ends;
+    self.ends = Some((a, b));
+  }
+
+  fn interpolated_ends(&self) -> Option<(Vec2, Vec2)> {
+    let (a, b) = self.ends?;
+    let (prev_a, prev_b) = self.prev_ends.unwrap_or((a, b));
+    let alpha: f32 = render_alpha();
+    Some((prev_a + (a - prev_a) * alpha, prev_b + (b - prev_b) * alpha))
+  }
+}
+
+impl NodeLike for Joint {
+  fn render(&mut self) {
+    self.base.render();
+    let Some((a, b)) = self.interpolated_ends().filter(|_| debug_physics()) else {
+      return
+    };
+    //? In world space, where 'length' is, before the camera's zoom scales the ends.
+    let taut: bool = self.length.is_none_or(|length| (b - a).length() >= length - 0.5);
+    let (a, b) = (world_to_screen(a), world_to_screen(b));
+    let color = self.color.into();
+    let line = |from: Vec2, to: Vec2, thickness: f32| draw_line(from.get_x(), from.get_y(), to.get_x(), to.get_y(), thickness, color);
+    match self.kind {
+      JointKind::Spring => {
+        let side: Vec2 = (b - a).perp().normalized() * 4.0;
+        let mut last: Vec2 = a;
+        for step in 1..SPRING_COILS * 2 {
+          let along: Vec2 = a + (b - a) * (step as f32 / (SPRING_COILS * 2) as f32);
+          let point: Vec2 = if step % 2 == 0 { along - side } else { along + side };
+          line(last, point, 1.0);
+          last = point;
+        }
+        line(last, b, 1.0);
+      },
+      JointKind::Rope => line(a, b, if taut { 2.0 } else { 1.0 }),
+      JointKind::Distance | JointKind::Pin => line(a, b, 2.0),
+    }
+    draw_circle(a.get_x(), a.get_y(), 3.0, color);
+    if self.kind == JointKind::Pin {
+      draw_circle_lines(b.get_x(), b.get_y(), 5.0, 1.5, color);
+    } else {
+      draw_circle(b.get_x(), b.get_y(), 3.0, color);
+    }
+  }
+  fn setup(&mut self) {
+    self.base.setup();
+  }
+  fn teardown(&mut self) {
+    self.base.teardown();
+  }
+  fn update(&mut self, deltatime: f32) {
+    self.base.update(deltatime);
+  }
+  fn get_scripts(&mut self) -> &mut ScriptManager {
+    self.base.get_scripts()
+  }
+  fn get_base(&mut self) -> &mut Node {
+    &mut self.base
+  }
+  fn get_layering(&mut self) -> Option<&mut Layering> {
+    Some(&mut self.layering)
+  }
+  fn get_kind(&self) -> &str {
+    "Joint"
+  }
+  fn get_id(&self) -> u64 {
+    self.base.id
+  }
+  fn lua_keys(&self) -> Vec<&'static str> {
+    vec!["kind", "body_a", "body_b", "anchor_a", "anchor_b", "length", "stiffness", "damping", "color", "z_index", "layer"]
+  }
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    match key {
+      "kind" => self.kind.name().into_lua(lua),
+      "body_a" => self.body_a.as_ref().and_then(|body| body.upgrade()).into_lua(lua),
+      "body_b" => self.body_b.as_ref().and_then(|body| body.upgrade()).into_lua(lua),
+      "anchor_a" => Place::field(this, |joint: &mut Joint| &mut joint.anchor_a).into_lua(lua),
+      "anchor_b" => Place::field(this, |joint: &mut Joint| &mut joint.anchor_b).into_lua(lua),
+      "length" => self.length.into_lua(lua),
+      "stiffness" => self.stiffness.into_lua(lua),
+      "damping" => self.damping.into_lua(lua),
+      "color" => Place::field(this, |joint: &mut Joint| &mut joint.color).into_lua(lua),
+      "z_index" | "layer" => self.layering.lua_get(lua, key),
+      _ => self.base.lua_get_common(lua, this, self.get_kind(), key)
+    }
+  }
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "kind" => self.kind = JointKind::parse(&String::from_lua(value, lua)?)?,
+      "body_a" => self.body_a = Option::<NodeHandle>::from_lua(value, lua)?.map(|body| body.downgrade()),
+      "body_b" => self.body_b = Option::<NodeHandle>::from_lua(value, lua)?.map(|body| body.downgrade()),
+      "anchor_a" => self.anchor_a = Vec2::from_lua(value, lua)?,
+      "anchor_b" => self.anchor_b = Vec2::from_lua(value, lua)?,
+      "length" => self.length = Option::<f32>::from_lua(value, lua)?,
+      "stiffness" => self.stiffness = f32::from_lua(value, lua)?,
+      "damping" => self.damping = f32::from_lua(value, lua)?,
+      "color" => self.color = Color::from_lua(value, lua)?,
+      "z_index" | "layer" => return self.layering.lua_set(lua, key, value),
+      _ => return Ok(false)
+    }
+    Ok(true)
+  }
+}
+
+impl Downcastable for Joint {
+  fn as_any(&mut self) -> &mut dyn Any {
+    self
+  }
+}
@@ -8,3 +8,8 @@ pub mod camera;
 pub mod soundplayer;
 pub mod collider;
 pub mod button;
+pub mod viewport;
+pub mod canvas_layer;
+pub mod rigidbody;
+pub mod character_body;
+pub mod joint;
@@ -1,111 +1,174 @@
-use std::{any::Any, error::Error};
+use std::any::Any;
 
-use mlua::{Lua, Table, Value};
+use mlua::{IntoLua, Lua, Table, Value};
 
-use crate::core::{children_container::ChildrenContainer, core::{Downcastable, Luable}, nodelike::{NodeLike, generate_id}, script_manager::ScriptManager};
+use crate::core::{children_container::ChildrenContainer, core::Downcastable, engine::{screen_to_world, world_to_screen}, nodelike::{NodeLike, generate_id}, nodes::collider::Collider, proxy::{ChildrenProxy, NodeHandle, WeakNodeHandle, bury, method}, script_manager::ScriptManager, transform::ParentFrame, vec2::Vec2};
 
 pub struct Node {
   pub id: u64,
-  children: ChildrenContainer<String, Box<dyn NodeLike + Send + Sync>>,
-  scripts: ScriptManager
+  pub children: ChildrenContainer<String, NodeHandle>,
+  pub parent: Option<WeakNodeHandle>,
+  scripts: ScriptManager,
+  extras: Option<Table>,
 }
 
 impl Node {
   pub fn new() -> Node {
-    Node { id: generate_id(), children: ChildrenContainer::new(), scripts: ScriptManager::new() }
+    Node { id: generate_id(), children: ChildrenContainer::new(), parent: None, scripts: ScriptManager::new(), extras: None }
   }
-  fn render_children(&mut self) {
-    self.children.foreach_child(|_, _, nodelike| {
-      nodelike.render();
-    });
-  }
-  
   fn update_children(&mut self, dt: f32) {
-    self.children.foreach_child(|_, _, nodelike| {
-      nodelike.update(dt);
+    self.children.foreach_child(|_, _, child| {
+      child.with(|nodelike| nodelike.update(dt));
     });
   }
-  fn setup_children(&mut self) {
-    self.children.foreach_child(|_, _, nodelike| {
-      nodelike.setup();
-    });
+
+  pub fn add_child(&mut self, name: String, child: NodeHandle) {
+    if let Some(old) = self.children.add_child(name, child) {
+      bury(old);
+    }
   }
-  pub fn load_scripts(&mut self, node_name: &str) {
-    self.scripts.loadScripts().expect(&format!("Cannot load scripts for {}<{}>", node_name, self.id));
+  pub fn remove_child(&mut self, name: &str) {
+    if let Some(old) = self.children.take_child(&name.to_string()) {
+      old.with(|nodelike| nodelike.get_base().parent = None);
+      Collider::moved(&old);
+      bury(old);
+    }
   }
-  pub fn add_kind_to_lua(kind: String, env: &Table, lua: &Lua) -> Result<(), Box<dyn Error>> {
-    env.set("kind", lua.create_function(move |_, ()| {
-      Ok(kind.clone())
-    })?)?;
+  pub fn clear_children(&mut self) {
+    for (_, old) in self.children.entries() {
+      old.with(|nodelike| nodelike.get_base().parent = None);
+      bury(old);
+    }
+    self.children.clear_children();
+  }
+
+  //? Fields a script sets on a node without a Rust counterpart are kept here instead of being dropped.
+  pub fn extras(&self) -> Option<Table> {
+    self.extras.clone()
+  }
+  pub fn get_extra(&self, key: &str) -> mlua::Result<Value> {
+    match &self.extras {
+      Some(extras) => extras.get(key),
+      None => Ok(Value::Nil)
+    }
+  }
+  pub fn set_extra(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<()> {
+    if self.extras.is_none() {
+      self.extras = Some(lua.create_table()?);
+    }
+    self.extras.as_ref().unwrap().set(key, value)
+  }
+
+  fn check_moved(moved: bool, name: &str) -> mlua::Result<()> {
+    if !moved {
+      return Err(mlua::Error::RuntimeError(format!("No child named '{}'", name)))
+    }
     Ok(())
   }
+
+  pub fn id_function(lua: &Lua, id: u64) -> mlua::Result<Value> {
+    Ok(Value::Function(lua.create_function(move |_, ()| {
+      Ok(id)
+    })?))
+  }
+  pub fn kind_function(lua: &Lua, kind: String) -> mlua::Result<Value> {
+    Ok(Value::Function(lua.create_function(move |_, ()| {
+      Ok(kind.clone())
+    })?))
+  }
+
+  pub fn lua_get_common(&self, lua: &Lua, this: &NodeHandle, kind: &str, key: &str) -> mlua::Result<Value> {
+    match key {
+      "id" => Node::id_function(lua, self.id),
+      "kind" => Node::kind_function(lua, kind.to_string()),
+      "base" => this.clone().into_lua(lua),
+      "children" => ChildrenProxy(this.clone()).into_lua(lua),
+      "add_child" => method(lua, "Node.add_child", |_, (this, name, child): (NodeHandle, String, NodeHandle)| {
+        this.add_child(name, child.clone());
+        Ok(child)
+      }),
+      "remove_child" => method(lua, "Node.remove_child", |_, (this, name): (NodeHandle, String)| {
+        this.with(|node| node.get_base().remove_child(&name));
+        Ok(())
+      }),
+      "to_global" => method(lua, "Node.to_global", |_, (this, point): (NodeHandle, Vec2)| {
+        Ok(this.global_frame().to_global(point))
+      }),
+      "to_local" => method(lua, "Node.to_local", |_, (this, point): (NodeHandle, Vec2)| {
+        Ok(this.global_frame().to_local(point))
+      }),
+      "to_screen" => method(lua, "Node.to_screen", |_, (this, point): (NodeHandle, Vec2)| {
+        let frame: ParentFrame = this.global_frame();
+        Ok(if frame.screen { frame.to_global(point) } else { world_to_screen(frame.to_global(point)) })
+      }),
+      "from_screen" => method(lua, "Node.from_screen", |_, (this, point): (NodeHandle, Vec2)| {
+        let frame: ParentFrame = this.global_frame();
+        Ok(frame.to_local(if frame.screen { point } else { screen_to_world(point) }))
+      }),
+      "clear_children" => method(lua, "Node.clear_children", |_, this: NodeHandle| {
+        this.with(|node| node.get_base().clear_children());
+        Ok(())
+      }),
+      //? Child positions are 1-based on the Lua side, like any other Lua sequence.
+      "move_child" => method(lua, "Node.move_child", |_, (this, name, index): (NodeHandle, String, usize)| {
+        let moved: bool = this.with(|node| node.get_base().children.move_child(&name, index.saturating_sub(1)));
+        Node::check_moved(moved, &name)
+      }),
+      "move_to_front" => method(lua, "Node.move_to_front", |_, (this, name): (NodeHandle, String)| {
+        let moved: bool = this.with(|node| node.get_base().children.move_to_front(&name));
+        Node::check_moved(moved, &name)
+      }),
+      "move_to_back" => method(lua, "Node.move_to_back", |_, (this, name): (NodeHandle, String)| {
+        let moved: bool = this.with(|node| node.get_base().children.move_to_back(&name));
+        Node::check_moved(moved, &name)
+      }),
+      "child_index" => method(lua, "Node.child_index", |_, (this, name): (NodeHandle, String)| {
+        Ok(this.with(|node| node.get_base().children.index_of(&name)).map(|index| index + 1))
+      }),
+      "child_at" => method(lua, "Node.child_at", |_, (this, index): (NodeHandle, usize)| {
+        let found: Option<(String, NodeHandle)> = this.with(|node| {
+          node.get_base().children.child_at(index.wrapping_sub(1)).map(|(name, child)| (name.clone(), child.clone()))
+        });
+        Ok(match found {
+          Some((name, child)) => (Some(child), Some(name)),
+          None => (None, None)
+        })
+      }),
+      _ => Ok(Value::Nil)
+    }
+  }
 }
 
 impl NodeLike for Node {
   fn setup(&mut self) {
-    self.setup_children();
   }
   fn render(&mut self) {
-    self.render_children();
   }
   fn update(&mut self, deltatime: f32) {
     self.update_children(deltatime);
   }
-  fn load_scripts(&mut self) {
-    let tmp = self.get_kind().to_string();
-    self.load_scripts(&tmp);
+  fn teardown(&mut self) {
   }
   fn get_scripts(&mut self) -> &mut ScriptManager {
     &mut self.scripts
   }
+  fn get_base(&mut self) -> &mut Node {
+    self
+  }
   fn get_kind(&self) -> &str {
     "Node"
   }
-
-}
-
-impl Luable for Node {
-  fn as_lua(&self, lua: &Lua) -> Result<Value, Box<dyn Error>> {
-    let table = lua.create_table()?;
-    let children: Table = lua.create_table()?;
-    for (id, node) in self.children.children.iter() {
-      let tmp = node.as_lua(lua).unwrap();
-      children.set(id.clone(), tmp)?;
-    }
-    table.set("children", children)?;
-    let id = self.id;
-    table.set("id", lua.create_function(move |_, ()| {
-      Ok(id)
-    })?)?;
-    table.set("scripts", self.scripts.as_lua(lua)?)?;
-    Node::add_kind_to_lua(self.get_kind().to_string(), &table, lua)?;
-
-    Ok(Value::Table(table))
+  fn get_id(&self) -> u64 {
+    self.id
   }
-
-  fn from_lua(&mut self, value: Value) -> Result<(), Box<dyn Error>> {
-    if let Some(tbl) = value.as_table() {
-      let children: Table = tbl.get("children")?;
-      if children.len()? > self.children.children.len() as i64 {
-        return Err("Cannot add Children in raw Lua".into());
-      }
-      let mut pairs: Vec<(String, Value)> = Vec::new();
-      children.for_each(|k, v| {
-        pairs.push((k, v));
-        Ok(())
-      })?;
-
-      for i in 0..children.len()? {
-        let pair = pairs.iter().nth(i as usize).unwrap();
-        self.children.children.get_mut(&pair.0).unwrap().from_lua(pair.1.clone())?;
-      }
-
-      self.scripts.from_lua(tbl.get("scripts")?)?;
-
-      return Ok(())
-    }
-
-    Err("Invalid Lua Value".into())
+  fn lua_keys(&self) -> Vec<&'static str> {
+    Vec::new()
+  }
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    self.lua_get_common(lua, this, self.get_kind(), key)
+  }
+  fn lua_set(&mut self, _: &Lua, _: &str, _: Value) -> mlua::Result<bool> {
+    Ok(false)
   }
 }
 
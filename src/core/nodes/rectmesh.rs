@@ -1,50 +1,82 @@
-use std::{any::Any, error::Error};
+use std::any::Any;
 
-use macroquad::shapes::draw_rectangle;
-use mlua::{Lua, Value};
+use macroquad::shapes::{DrawRectangleParams, draw_rectangle_ex};
+use mlua::{FromLua, IntoLua, Lua, Value};
 
-use crate::core::{color::Color, core::{Downcastable, Luable}, engine::main_camera, nodelike::NodeLike, nodes::node::Node, script_manager::ScriptManager, transform::Transform, vec2::Vec2};
+use crate::core::{color::Color, core::Downcastable, layering::Layering, nodelike::NodeLike, nodes::node::Node, proxy::{NodeHandle, Place}, script_manager::ScriptManager, transform::{ScreenRect, Transform}, vec2::Vec2};
 
 pub struct RectMesh {
   base: Node,
+  pub layering: Layering,
   pub transform: Transform,
   pub color: Color
 }
 
 impl RectMesh {
   pub fn new(pos: Vec2, size: Vec2, color: Color) -> RectMesh {
-    RectMesh { base: Node::new(), transform: Transform::new(pos, size), color: color }
+    RectMesh { base: Node::new(), layering: Layering::new(), transform: Transform::new(pos, size), color: color }
   }
 }
 
 impl NodeLike for RectMesh {
   fn render(&mut self) {
     self.base.render();
-    let (actual_position, actual_size): (Vec2, Vec2) = self.transform.get_camera_relative();
-    draw_rectangle(
-      actual_position.get_x() as f32, 
-      actual_position.get_y() as f32, 
-      actual_size.get_x() as f32, 
-      actual_size.get_y() as f32, 
-      self.color.into()
+    let rect: ScreenRect = self.transform.get_camera_relative();
+    draw_rectangle_ex(
+      rect.pos.get_x(), 
+      rect.pos.get_y(), 
+      rect.size.get_x(), 
+      rect.size.get_y(), 
+      DrawRectangleParams { offset: rect.origin.into(), rotation: rect.rotation, color: self.color.into() }
     );
   }
   fn setup(&mut self) {
     self.base.setup();
   }
+  fn teardown(&mut self) {
+    self.base.teardown();
+  }
   fn update(&mut self, deltatime: f32) {
     self.base.update(deltatime);
   }
-  fn load_scripts(&mut self) {
-    let tmp = self.get_kind().to_string();
-    self.base.load_scripts(&tmp);
-  }
   fn get_scripts(&mut self) -> &mut ScriptManager {
     self.base.get_scripts()
   }
+  fn get_base(&mut self) -> &mut Node {
+    &mut self.base
+  }
+  fn get_layering(&mut self) -> Option<&mut Layering> {
+    Some(&mut self.layering)
+  }
+  fn get_transform(&mut self) -> Option<&mut Transform> {
+    Some(&mut self.transform)
+  }
   fn get_kind(&self) -> &str {
     "RectMesh"
   }
+  fn get_id(&self) -> u64 {
+    self.base.id
+  }
+  fn lua_keys(&self) -> Vec<&'static str> {
+    vec!["transform", "color", "z_index", "layer"]
+  }
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    match key {
+      "transform" => Place::field(this, |mesh: &mut RectMesh| &mut mesh.transform).into_lua(lua),
+      "color" => Place::field(this, |mesh: &mut RectMesh| &mut mesh.color).into_lua(lua),
+      "z_index" | "layer" => self.layering.lua_get(lua, key),
+      _ => self.base.lua_get_common(lua, this, self.get_kind(), key)
+    }
+  }
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "transform" => self.transform.replace(Transform::from_lua(value, lua)?),
+      "color" => self.color = Color::from_lua(value, lua)?,
+      "z_index" | "layer" => return self.layering.lua_set(lua, key, value),
+      _ => return Ok(false)
+    }
+    Ok(true)
+  }
 }
 
 impl Downcastable for RectMesh {
@@ -52,34 +84,3 @@ impl Downcastable for RectMesh {
     self
   }
 }
-
-impl Luable for RectMesh {
-  fn as_lua(&self, lua: &Lua) -> Result<Value, Box<dyn Error>> {
-    let table = lua.create_table()?;
-    let base: Value = self.base.as_lua(lua)?;
-    table.set("base", base)?;
-
-    let transform = self.transform.as_lua(lua)?;
-    table.set("transform", transform)?;
-
-    let color = self.color.as_lua(lua)?;
-    table.set("color", color)?;
-    Node::add_kind_to_lua(self.get_kind().to_string(), &table, lua)?;
-
-    Ok(Value::Table(table))
-  }
-
-  fn from_lua(&mut self, value: Value) -> Result<(), Box<dyn Error>> {
-    if let Some(tbl) = value.as_table() {
-      let base: Value = tbl.get("base")?;
-      self.base.from_lua(base)?;
-      let transform: Value = tbl.get("transform")?;
-      self.transform.from_lua(transform)?;
-      let color: Value = tbl.get("color")?;
-      self.color.from_lua(color)?;
-      return Ok(())
-    }
-
-    Err("Invalid Lua Value".into())
-  }
-}
@@ -0,0 +1,136 @@
+use mlua::{FromLua, IntoLua, Lua, Value};
+
+use crate::core::{core::Downcastable, nodelike::NodeLike, nodes::node::Node, proxy::{NodeHandle, Place, method}, script_manager::ScriptManager, transform::Transform, vec2::Vec2};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BodyMode {
+  Static,
+  Kinematic,
+  Dynamic,
+}
+
+impl BodyMode {
+  pub fn parse(name: &str) -> mlua::Result<BodyMode> {
+    match name {
+      "static" => Ok(BodyMode::Static),
+      "kinematic" => Ok(BodyMode::Kinematic),
+      "dynamic" => Ok(BodyMode::Dynamic),
+      _ => Err(mlua::Error::RuntimeError(format!("Unknown body mode '{}', expected static, kinematic or dynamic", name)))
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      BodyMode::Static => "static",
+      BodyMode::Kinematic => "kinematic",
+      BodyMode::Dynamic => "dynamic",
+    }
+  }
+}
+
+//? Bodies only translate: collisions never make them spin.
+pub struct RigidBody {
+  base: Node,
+  pub transform: Transform,
+  pub mode: BodyMode,
+  pub mass: f32,
+  pub velocity: Vec2,
+  pub gravity_scale: f32,
+  pub friction: f32,
+  pub restitution: f32,
+  pub force: Vec2,
+}
+
+impl RigidBody {
+  pub fn new(pos: Vec2, mode: BodyMode) -> RigidBody {
+    RigidBody {
+      base: Node::new(),
+      transform: Transform::new(pos, Vec2::ZERO),
+      mode,
+      mass: 1.0,
+      velocity: Vec2::ZERO,
+      gravity_scale: 1.0,
+      friction: 0.2,
+      restitution: 0.0,
+      force: Vec2::ZERO,
+    }
+  }
+
+  pub fn inverse_mass(&self) -> f32 {
+    if self.mode == BodyMode::Dynamic && self.mass > 0.0 { 1.0 / self.mass } else { 0.0 }
+  }
+
+  pub fn apply_impulse(&mut self, impulse: Vec2) {
+    self.velocity += impulse * self.inverse_mass();
+  }
+}
+
+impl NodeLike for RigidBody {
+  fn get_kind(&self) -> &str {
+    "RigidBody"
+  }
+  fn get_id(&self) -> u64 {
+    self.base.id
+  }
+  fn get_scripts(&mut self) -> &mut ScriptManager {
+    self.base.get_scripts()
+  }
+  fn get_base(&mut self) -> &mut Node {
+    &mut self.base
+  }
+  fn get_transform(&mut self) -> Option<&mut Transform> {
+    Some(&mut self.transform)
+  }
+  fn render(&mut self) {
+    self.base.render();
+  }
+  fn setup(&mut self) {
+    self.base.setup();
+  }
+  fn teardown(&mut self) {
+    self.base.teardown();
+  }
+  fn update(&mut self, deltatime: f32) {
+    self.base.update(deltatime);
+  }
+  fn lua_keys(&self) -> Vec<&'static str> {
+    vec!["transform", "mode", "mass", "velocity", "gravity_scale", "friction", "restitution"]
+  }
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    match key {
+      "transform" => Place::field(this, |body: &mut RigidBody| &mut body.transform).into_lua(lua),
+      "mode" => self.mode.name().into_lua(lua),
+      "mass" => self.mass.into_lua(lua),
+      "velocity" => Place::field(this, |body: &mut RigidBody| &mut body.velocity).into_lua(lua),
+      "gravity_scale" => self.gravity_scale.into_lua(lua),
+      "friction" => self.friction.into_lua(lua),
+      "restitution" => self.restitution.into_lua(lua),
+      "apply_impulse" => method(lua, "RigidBody.apply_impulse", |_, (this, impulse): (NodeHandle, Vec2)| {
+        this.cast(|body: &mut RigidBody| body.apply_impulse(impulse)).ok_or_else(|| mlua::Error::RuntimeError("Node is not a RigidBody".to_string()))
+      }),
+      "apply_force" => method(lua, "RigidBody.apply_force", |_, (this, force): (NodeHandle, Vec2)| {
+        this.cast(|body: &mut RigidBody| body.force += force).ok_or_else(|| mlua::Error::RuntimeError("Node is not a RigidBody".to_string()))
+      }),
+      _ => self.base.lua_get_common(lua, this, self.get_kind(), key)
+    }
+  }
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "transform" => self.transform.replace(Transform::from_lua(value, lua)?),
+      "mode" => self.mode = BodyMode::parse(&String::from_lua(value, lua)?)?,
+      "mass" => self.mass = f32::from_lua(value, lua)?,
+      "velocity" => self.velocity = Vec2::from_lua(value, lua)?,
+      "gravity_scale" => self.gravity_scale = f32::from_lua(value, lua)?,
+      "friction" => self.friction = f32::from_lua(value, lua)?,
+      "restitution" => self.restitution = f32::from_lua(value, lua)?,
+      _ => return Ok(false)
+    }
+    Ok(true)
+  }
+}
+
+impl Downcastable for RigidBody {
+  fn as_any(&mut self) -> &mut dyn std::any::Any {
+    self
+  }
+}
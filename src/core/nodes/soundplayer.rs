@@ -2,10 +2,10 @@ use std::{collections::HashMap, sync::Mutex};
 
 use futures::{executor::block_on};
 use macroquad::audio::{PlaySoundParams, Sound, load_sound, play_sound};
-use mlua::{AnyUserData, Function, IntoLua, Table, UserData, Value};
+use mlua::{FromLua, IntoLua, Lua, Value};
 use once_cell::sync::Lazy;
 
-use crate::core::{core::{Downcastable, Luable}, nodelike::NodeLike, nodes::node::Node};
+use crate::core::{core::Downcastable, engine::is_headless, nodelike::NodeLike, nodes::node::Node, proxy::{NodeHandle, method}, script_manager::ScriptManager};
 
 static AUDIO_MANAGER: Lazy<Mutex<HashMap<String, Sound>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
@@ -16,20 +16,35 @@ pub struct SoundPlayer {
 
 impl SoundPlayer {
   pub fn new(sound: &str) -> SoundPlayer {
+    SoundPlayer::load(sound);
+    SoundPlayer {
+      base: Node::new(),
+      sound: sound.to_string()
+    }
+  }
+
+  fn load(sound: &str) {
+    if is_headless() || sound.is_empty() || AUDIO_MANAGER.lock().unwrap().contains_key(sound) {
+      return;
+    }
     let temp = sound.to_string();
     let audio = block_on( load_sound(&temp) ).expect(&format!("Cannot load sound {}", temp));
     AUDIO_MANAGER.lock().as_mut().expect("Failed to get AudioManager").insert(
       temp.clone(),
       audio
     );
-    SoundPlayer {
-      base: Node::new(),
-      sound: sound.to_string()
-    }
   }
 
-  pub fn empty() -> SoundPlayer {
-    SoundPlayer { base: Node::new(), sound: String::new() }
+  pub fn play(&self, looped: bool, volume: f32) {
+    if is_headless() {
+      return;
+    }
+    let manager = AUDIO_MANAGER.lock().unwrap();
+    let tmp = manager.get(&self.sound).unwrap();
+    play_sound(
+      tmp,
+      PlaySoundParams { looped: looped, volume: volume }
+    );
   }
 }
 
@@ -37,12 +52,14 @@ impl NodeLike for SoundPlayer {
   fn get_kind(&self) -> &str {
     "SoundPlayer"
   }
-  fn get_scripts(&mut self) -> &mut crate::core::script_manager::ScriptManager {
+  fn get_id(&self) -> u64 {
+    self.base.id
+  }
+  fn get_scripts(&mut self) -> &mut ScriptManager {
     self.base.get_scripts()
   }
-  fn load_scripts(&mut self) {
-    let tmp = self.get_kind().to_string();
-    self.base.load_scripts(&tmp);
+  fn get_base(&mut self) -> &mut Node {
+    &mut self.base
   }
   fn render(&mut self) {
     self.base.render();
@@ -50,35 +67,33 @@ impl NodeLike for SoundPlayer {
   fn setup(&mut self) {
     self.base.setup();
   }
+  fn teardown(&mut self) {
+    self.base.teardown();
+  }
   fn update(&mut self, deltatime: f32) {
     self.base.update(deltatime);
   }
-}
-
-impl Luable for SoundPlayer {
-  fn as_lua(&self, lua: &mlua::Lua) -> Result<mlua::Value, Box<dyn std::error::Error>> {
-    let table = lua.create_table()?;
-    table.set("base", self.base.as_lua(lua)?)?;
-    table.set("sound", self.sound.clone())?;
-    Node::add_kind_to_lua(self.get_kind().to_string(), &table, lua)?;
-    table.set("play", lua.create_function(|_, (this, looped, volume): (Table, bool, f32)| {
-      let sound: String = this.get::<String>("sound")?;
-      let manager = AUDIO_MANAGER.lock().unwrap();
-      let tmp = manager.get(&sound).unwrap();
-      play_sound(
-        tmp,
-        PlaySoundParams { looped: looped, volume: volume }
-      );
-      Ok(())
-    })?)?;
-    Ok(Value::Table(table)) 
+  fn lua_keys(&self) -> Vec<&'static str> {
+    vec!["sound"]
   }
-
-  fn from_lua(&mut self, value: mlua::Value) -> Result<(), Box<dyn std::error::Error>> {
-    let table = value.as_table().ok_or("Invalid Lua Value")?;
-    self.base.from_lua(table.get("base")?)?;
-    self.sound = table.get("sound")?;
-    Ok(())
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    match key {
+      "sound" => self.sound.clone().into_lua(lua),
+      "play" => method(lua, "SoundPlayer.play", |_, (this, looped, volume): (NodeHandle, bool, f32)| {
+        this.cast(|player: &mut SoundPlayer| player.play(looped, volume)).ok_or_else(|| mlua::Error::RuntimeError("Node is not a SoundPlayer".to_string()))
+      }),
+      _ => self.base.lua_get_common(lua, this, self.get_kind(), key)
+    }
+  }
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "sound" => {
+        self.sound = String::from_lua(value, lua)?;
+        SoundPlayer::load(&self.sound);
+      },
+      _ => return Ok(false)
+    }
+    Ok(true)
   }
 }
 
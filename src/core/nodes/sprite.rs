@@ -1,62 +1,72 @@
-use mlua::Value;
+use mlua::{FromLua, IntoLua, Lua, Value};
 
-use crate::core::{core::{Downcastable, Luable}, engine::main_camera, image::Img, nodelike::NodeLike, nodes::node::Node, transform::Transform, vec2::Vec2};
+use crate::core::{core::Downcastable, image::Img, layering::Layering, nodelike::NodeLike, nodes::node::Node, proxy::{NodeHandle, Place}, script_manager::ScriptManager, transform::Transform, vec2::Vec2};
 
 
 pub struct Sprite {
   base: Node,
+  pub layering: Layering,
   transform: Transform,
   img: Img
 }
 
 impl Sprite {
   pub fn new(pos: Vec2, size: Vec2, img: Img) -> Sprite {
-    Sprite { base: Node::new(), transform: Transform::new(pos, size), img }
+    Sprite { base: Node::new(), layering: Layering::new(), transform: Transform::new(pos, size), img }
   }
 }
 
 impl NodeLike for Sprite {
-  fn get_scripts(&mut self) -> &mut crate::core::script_manager::ScriptManager {
+  fn get_scripts(&mut self) -> &mut ScriptManager {
     self.base.get_scripts()
   }
-  fn load_scripts(&mut self) {
-    let tmp = self.get_kind().to_string();
-    self.base.load_scripts(&tmp);
+  fn get_base(&mut self) -> &mut Node {
+    &mut self.base
+  }
+  fn get_layering(&mut self) -> Option<&mut Layering> {
+    Some(&mut self.layering)
+  }
+  fn get_transform(&mut self) -> Option<&mut Transform> {
+    Some(&mut self.transform)
   }
   fn setup(&mut self) {
     self.base.setup();
   }
+  fn teardown(&mut self) {
+    self.base.teardown();
+  }
   fn update(&mut self, deltatime: f32) {
     self.base.update(deltatime);
   }
   fn render(&mut self) {
     self.base.render();
-    let (actual_position, actual_size): (Vec2, Vec2) = self.transform.get_camera_relative();
-    self.img.render(actual_position, actual_size);
+    self.img.render(&self.transform.get_camera_relative());
   }
   fn get_kind(&self) -> &str {
     "Sprite"
   }
-}
-
-impl Luable for Sprite {
-  fn as_lua(&self, lua: &mlua::Lua) -> Result<mlua::Value, Box<dyn std::error::Error>> {
-    let table = lua.create_table()?;
-    table.set("base", self.base.as_lua(lua)?)?;
-    table.set("transform", self.transform.as_lua(lua)?)?;
-    table.set("img", self.img.as_lua(lua)?)?;
-    Node::add_kind_to_lua(self.get_kind().to_string(), &table, lua)?;
-    Ok(Value::Table(table))
+  fn get_id(&self) -> u64 {
+    self.base.id
   }
-
-  fn from_lua(&mut self, value: Value) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(table) = value.as_table() {
-      self.base.from_lua(table.get("base")?)?;
-      self.transform.from_lua(table.get("transform")?)?;
-      self.img.from_lua(table.get("img")?)?;
-      return Ok(())
+  fn lua_keys(&self) -> Vec<&'static str> {
+    vec!["transform", "img", "z_index", "layer"]
+  }
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    match key {
+      "transform" => Place::field(this, |sprite: &mut Sprite| &mut sprite.transform).into_lua(lua),
+      "img" => Place::field(this, |sprite: &mut Sprite| &mut sprite.img).into_lua(lua),
+      "z_index" | "layer" => self.layering.lua_get(lua, key),
+      _ => self.base.lua_get_common(lua, this, self.get_kind(), key)
+    }
+  }
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "transform" => self.transform.replace(Transform::from_lua(value, lua)?),
+      "img" => self.img = Img::from_lua(value, lua)?,
+      "z_index" | "layer" => return self.layering.lua_set(lua, key, value),
+      _ => return Ok(false)
     }
-    Err("Invalid Lua Value".into())
+    Ok(true)
   }
 }
 
@@ -1,11 +1,12 @@
 use futures::executor::block_on;
 use macroquad::text::{Font, TextDimensions, TextParams, draw_text_ex, load_ttf_font, measure_text};
-use mlua::{AnyUserData, IntoLua, Table, UserData, Value};
+use mlua::{FromLua, IntoLua, Lua, Value};
 
-use crate::core::{color::Color, core::{Downcastable, Luable}, engine::main_camera, nodelike::NodeLike, nodes::node::Node, transform::Transform, vec2::Vec2};
+use crate::core::{color::Color, core::Downcastable, engine::{is_headless, main_camera}, layering::Layering, nodelike::NodeLike, nodes::node::Node, proxy::{NodeHandle, Place, method}, script_manager::ScriptManager, transform::ParentFrame, vec2::Vec2};
 
 pub struct Text {
   base: Node,
+  pub layering: Layering,
   text: String,
   pos: Vec2,
   scale: f32,
@@ -15,12 +16,13 @@ pub struct Text {
   font_path: Option<String>,
   rotation: f32,
   color: Color,
+  parent: ParentFrame,
 }
 
 impl Text {
   pub fn new(text: &str, pos: Vec2, size: u16, color: Color) -> Text {
     Text { 
-      base: Node::new(), 
+      base: Node::new(), layering: Layering::new(), 
       text: text.to_string(), 
       pos: pos, 
       scale: 1.0, 
@@ -30,11 +32,12 @@ impl Text {
       font_path: None,
       rotation: 0.0, 
       color: color, 
+      parent: ParentFrame::IDENTITY,
     }
   }
 
   fn load_font(&mut self) {
-    self.font = if self.font_path.is_some() {
+    self.font = if self.font_path.is_some() && !is_headless() {
       let tmp: &str = &self.font_path.as_ref().unwrap();
       Some( block_on( load_ttf_font(tmp) ).expect(&format!("Cannot load Font {}", tmp)) )
     } else {
@@ -42,14 +45,22 @@ impl Text {
     }
   }
 
+  fn estimate_size(text: &str, font_size: u16, scale: f32) -> Vec2 {
+    let height = font_size as f32 * scale;
+    Vec2::new(text.chars().count() as f32 * height * 0.5, height)
+  }
+
   pub fn getTextSize(&self) -> Vec2 {
+    if is_headless() {
+      return Text::estimate_size(&self.text, self.font_size, self.scale);
+    }
     let temp = measure_text(
       &self.text, 
       self.font.as_ref(), 
       self.font_size, 
       self.scale, 
     );
-    Vec2::new(temp.width as i32, temp.height as i32)
+    Vec2::new(temp.width, temp.height)
   }
 }
 
@@ -57,29 +68,52 @@ impl NodeLike for Text {
   fn get_kind(&self) -> &str {
     "Text"
   }
-  fn get_scripts(&mut self) -> &mut crate::core::script_manager::ScriptManager {
+  fn get_id(&self) -> u64 {
+    self.base.id
+  }
+  fn get_scripts(&mut self) -> &mut ScriptManager {
     self.base.get_scripts()  
   }
-  fn load_scripts(&mut self) {
-    let tmp = self.get_kind().to_string();
-    self.base.load_scripts(&tmp);
+  fn get_base(&mut self) -> &mut Node {
+    &mut self.base
+  }
+  fn get_layering(&mut self) -> Option<&mut Layering> {
+    Some(&mut self.layering)
+  }
+  fn sort_y(&mut self) -> f32 {
+    self.parent.to_global(self.pos).get_y()
+  }
+  fn get_position(&mut self) -> Option<&mut Vec2> {
+    Some(&mut self.pos)
+  }
+  fn propagate(&mut self, parent: ParentFrame) -> ParentFrame {
+    self.parent = parent;
+    parent.offset(self.pos, self.pos)
   }
   fn setup(&mut self) {
     self.base.setup();
   }
+  fn teardown(&mut self) {
+    self.base.teardown();
+  }
   fn render(&mut self) {
     self.base.render();
 
-    let scale = if let Some(cam) = main_camera().as_ref() {
-      self.scale / cam.focal_length
-    } else {
-      self.scale
+    //? Fonts only scale uniformly, so the parent's Y scale sizes the glyphs and its X scale stretches them through the aspect.
+    let (scale_x, scale_y) = (self.parent.scale.get_x(), self.parent.scale.get_y());
+    let aspect: f32 = if scale_y == 0.0 { self.aspect } else { self.aspect * scale_x / scale_y };
+    let (pos, scale, rotation): (Vec2, f32, f32) = {
+      let (pos, scale, rotation) = (self.parent.to_global_interpolated(self.pos), self.scale * scale_y, self.rotation + self.parent.rotation);
+      match main_camera().filter(|_| !self.parent.screen) {
+        Some(cam) => (cam.to_screen(pos), scale * cam.zoom, rotation - cam.rotation),
+        None => (pos, scale, rotation)
+      }
     };
 
     draw_text_ex(
       &self.text, 
-      self.pos.get_x() as f32, 
-      self.pos.get_y() as f32, 
+      pos.get_x(), 
+      pos.get_y(), 
       TextParams { 
         font: if self.font.is_some() {
           let tmp = self.font.as_ref().unwrap();
@@ -87,8 +121,8 @@ impl NodeLike for Text {
         } else { None }, 
         font_size: self.font_size, 
         font_scale: scale, 
-        font_scale_aspect: self.aspect, 
-        rotation: self.rotation, 
+        font_scale_aspect: aspect, 
+        rotation, 
         color: self.color.into()
       }
     );
@@ -96,69 +130,47 @@ impl NodeLike for Text {
   fn update(&mut self, deltatime: f32) {
     self.base.update(deltatime);
   }
-}
-
-impl Luable for Text {
-  fn as_lua(&self, lua: &mlua::Lua) -> Result<mlua::Value, Box<dyn std::error::Error>> {
-    let table = lua.create_table()?;
-
-    table.set("base", self.base.as_lua(lua)?)?;
-    table.set("text", self.text.clone())?;
-    table.set("pos", self.pos.as_lua(lua)?)?;
-    table.set("scale", self.scale)?;
-    table.set("aspect", self.aspect)?;
-    table.set("font_size", self.font_size)?;
-    table.set("rotation", self.rotation)?;
-    table.set("color", self.color.as_lua(lua)?)?;
-    table.set("font", self.font_path.clone().unwrap_or("".to_string()).into_lua(lua)?)?;
-
-    table.set("dimensions", lua.create_function(|thislua, this: Table| {
-      let font = {
-        let font_path: String = this.get("font")?;
-        if font_path.is_empty() {
-          None
-        } else {
-          Some(&block_on( load_ttf_font(&font_path) ).expect(&format!("Cannot load Font {}", &font_path)))
-        }
-      };
-      let dims = measure_text(
-        &this.get::<String>("text")?, 
-        font, 
-        this.get("font_size")?, 
-        this.get("scale")?, 
-      );
-      Ok(Vec2::new(dims.width as i32, dims.height as i32).as_lua(thislua).expect("Invalid Lua Value"))
-    })?)?;
-    
-    Node::add_kind_to_lua(self.get_kind().to_string(), &table, lua)?;
-
-    Ok(Value::Table(table))
+  fn lua_keys(&self) -> Vec<&'static str> {
+    vec!["text", "pos", "scale", "aspect", "font_size", "rotation", "color", "font", "z_index", "layer"]
   }
-  fn from_lua(&mut self, value: mlua::Value) -> Result<(), Box<dyn std::error::Error>> {
-    let table: &Table = value.as_table().ok_or("Invalid Lua Value".to_string())?;
-
-    self.base.from_lua(table.get("base")?)?;
-    self.text = table.get::<String>("text")?;
-    self.pos.from_lua(table.get("pos")?)?;
-    self.scale = table.get("scale")?;
-    self.aspect = table.get("aspect")?;
-    self.font_size = table.get("font_size")?;
-    let old_path = self.font_path.clone().unwrap_or(String::new());
-    self.font_path = {
-      let tmp = table.get::<String>("font")?;
-      if tmp.is_empty() {
-        None
-      } else {
-        Some(tmp)
-      }
-    };
-    if !(self.font_path.clone().is_none() && old_path.is_empty() || self.font_path.clone().unwrap() == old_path) {
-      self.load_font();
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    match key {
+      "text" => self.text.clone().into_lua(lua),
+      "pos" => Place::field(this, |text: &mut Text| &mut text.pos).into_lua(lua),
+      "scale" => self.scale.into_lua(lua),
+      "aspect" => self.aspect.into_lua(lua),
+      "font_size" => self.font_size.into_lua(lua),
+      "rotation" => self.rotation.into_lua(lua),
+      "color" => Place::field(this, |text: &mut Text| &mut text.color).into_lua(lua),
+      "font" => self.font_path.clone().unwrap_or_default().into_lua(lua),
+      "dimensions" => method(lua, "Text.dimensions", |_, this: NodeHandle| {
+        this.cast(|text: &mut Text| text.getTextSize()).ok_or_else(|| mlua::Error::RuntimeError("Node is not a Text".to_string()))
+      }),
+      "z_index" | "layer" => self.layering.lua_get(lua, key),
+      _ => self.base.lua_get_common(lua, this, self.get_kind(), key)
     }
-    self.rotation = table.get("rotation")?;
-    self.color.from_lua(table.get("color")?)?;
-
-    Ok(())
+  }
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "text" => self.text = String::from_lua(value, lua)?,
+      "pos" => self.pos = Vec2::from_lua(value, lua)?,
+      "scale" => self.scale = f32::from_lua(value, lua)?,
+      "aspect" => self.aspect = f32::from_lua(value, lua)?,
+      "font_size" => self.font_size = u16::from_lua(value, lua)?,
+      "rotation" => self.rotation = f32::from_lua(value, lua)?,
+      "color" => self.color = Color::from_lua(value, lua)?,
+      "font" => {
+        let path: String = String::from_lua(value, lua)?;
+        let path: Option<String> = if path.is_empty() { None } else { Some(path) };
+        if path != self.font_path {
+          self.font_path = path;
+          self.load_font();
+        }
+      },
+      "z_index" | "layer" => return self.layering.lua_set(lua, key, value),
+      _ => return Ok(false)
+    }
+    Ok(true)
   }
 }
 
@@ -0,0 +1,175 @@
+use macroquad::{camera::{Camera2D, set_camera}, shapes::draw_rectangle, texture::{FilterMode, RenderTarget, render_target}};
+use mlua::{FromLua, IntoLua, Lua, Value};
+
+use crate::core::{color::Color, core::Downcastable, engine::is_headless, image::Img, nodelike::NodeLike, nodes::{camera::{Camera, CameraView}, node::Node}, proxy::{NodeHandle, Place, WeakNodeHandle, method}, script_manager::ScriptManager, stretch::{screen_camera, screen_viewport, target_camera, use_base_camera}, transform::ParentFrame, vec2::Vec2};
+
+
+pub struct Viewport {
+  base: Node,
+  pub pos: Vec2,
+  pub size: Vec2,
+  camera: Option<NodeHandle>,
+  source: Option<WeakNodeHandle>,
+  clear_color: Option<Color>,
+  to_texture: bool,
+  target: Option<RenderTarget>,
+}
+
+pub struct ViewportPass {
+  pub world: NodeHandle,
+  pub camera: Option<NodeHandle>,
+}
+
+impl Viewport {
+  pub fn new(pos: Vec2, size: Vec2, camera: Option<NodeHandle>) -> Viewport {
+    Viewport { base: Node::new(), pos, size, camera, source: None, clear_color: None, to_texture: false, target: None }
+  }
+
+  pub fn camera(&self) -> Option<NodeHandle> {
+    self.camera.clone()
+  }
+
+  pub fn draws_to_texture(&self) -> bool {
+    self.to_texture
+  }
+
+  fn ensure_target(&mut self) -> Option<RenderTarget> {
+    if !self.to_texture || is_headless() {
+      self.target = None;
+      return None
+    }
+    let (width, height) = (self.size.get_x().max(1.0) as u32, self.size.get_y().max(1.0) as u32);
+    let outdated: bool = match &self.target {
+      Some(target) => target.texture.width() as u32 != width || target.texture.height() as u32 != height,
+      None => true
+    };
+    if outdated {
+      let target: RenderTarget = render_target(width, height);
+      target.texture.set_filter(FilterMode::Nearest);
+      self.target = Some(target);
+    }
+    self.target.clone()
+  }
+
+  pub fn texture(&mut self) -> Img {
+    Img::from_texture(self.ensure_target().map(|target| target.texture))
+  }
+
+  pub fn begin_pass(&mut self, this: &NodeHandle) -> Option<ViewportPass> {
+    if is_headless() || self.size.get_x() <= 0.0 || self.size.get_y() <= 0.0 {
+      return None
+    }
+    let camera: Camera2D = match self.ensure_target() {
+      Some(target) => target_camera(self.size, target),
+      None => Camera2D { viewport: Some(screen_viewport(self.pos, self.size)), ..screen_camera(self.size) }
+    };
+    set_camera(&camera);
+    if let Some(color) = self.clear_color {
+      draw_rectangle(0.0, 0.0, self.size.get_x(), self.size.get_y(), color.into());
+    }
+    let world: NodeHandle = self.source.as_ref().and_then(|source| source.upgrade()).unwrap_or_else(|| this.clone());
+    Some(ViewportPass { world, camera: self.camera.clone() })
+  }
+
+  pub fn end_pass() {
+    use_base_camera();
+  }
+
+  pub fn view(camera: &Option<NodeHandle>) -> Option<CameraView> {
+    camera.as_ref().and_then(|cam| cam.cast(|cam: &mut Camera| cam.view()))
+  }
+
+  pub fn screen_to_world(&self, point: Vec2) -> Vec2 {
+    let local: Vec2 = if self.to_texture { point } else { point - self.pos };
+    match Viewport::view(&self.camera) {
+      Some(view) => view.to_world(local),
+      None => local
+    }
+  }
+
+  pub fn world_to_screen(&self, point: Vec2) -> Vec2 {
+    let local: Vec2 = match Viewport::view(&self.camera) {
+      Some(view) => view.to_screen(point),
+      None => point
+    };
+    if self.to_texture { local } else { local + self.pos }
+  }
+}
+
+impl NodeLike for Viewport {
+  fn get_kind(&self) -> &str {
+    "Viewport"
+  }
+  fn get_id(&self) -> u64 {
+    self.base.id
+  }
+  fn get_scripts(&mut self) -> &mut ScriptManager {
+    self.base.get_scripts()
+  }
+  fn get_base(&mut self) -> &mut Node {
+    &mut self.base
+  }
+  //? The children live in the viewport's own world, so nothing above it moves them.
+  fn propagate(&mut self, _: ParentFrame) -> ParentFrame {
+    ParentFrame::IDENTITY
+  }
+  fn render(&mut self) {
+    self.base.render();
+  }
+  fn setup(&mut self) {
+    self.base.setup();
+  }
+  fn teardown(&mut self) {
+    self.base.teardown();
+  }
+  fn update(&mut self, deltatime: f32) {
+    self.base.update(deltatime);
+  }
+  fn lua_keys(&self) -> Vec<&'static str> {
+    vec!["pos", "size", "camera", "source", "clear_color", "to_texture"]
+  }
+  fn lua_get(&self, lua: &Lua, this: &NodeHandle, key: &str) -> mlua::Result<Value> {
+    match key {
+      "pos" => Place::field(this, |vp: &mut Viewport| &mut vp.pos).into_lua(lua),
+      "size" => Place::field(this, |vp: &mut Viewport| &mut vp.size).into_lua(lua),
+      "camera" => self.camera.clone().into_lua(lua),
+      "source" => self.source.as_ref().and_then(|source| source.upgrade()).into_lua(lua),
+      "clear_color" => self.clear_color.into_lua(lua),
+      "to_texture" => self.to_texture.into_lua(lua),
+      "texture" => method(lua, "Viewport.texture", |_, this: NodeHandle| {
+        this.cast(|vp: &mut Viewport| vp.texture()).ok_or_else(|| mlua::Error::RuntimeError("Node is not a Viewport".to_string()))
+      }),
+      "screen_to_world" => method(lua, "Viewport.screen_to_world", |_, (this, point): (NodeHandle, Vec2)| {
+        this.cast(|vp: &mut Viewport| vp.screen_to_world(point)).ok_or_else(|| mlua::Error::RuntimeError("Node is not a Viewport".to_string()))
+      }),
+      "world_to_screen" => method(lua, "Viewport.world_to_screen", |_, (this, point): (NodeHandle, Vec2)| {
+        this.cast(|vp: &mut Viewport| vp.world_to_screen(point)).ok_or_else(|| mlua::Error::RuntimeError("Node is not a Viewport".to_string()))
+      }),
+      _ => self.base.lua_get_common(lua, this, self.get_kind(), key)
+    }
+  }
+  fn lua_set(&mut self, lua: &Lua, key: &str, value: Value) -> mlua::Result<bool> {
+    match key {
+      "pos" => self.pos = Vec2::from_lua(value, lua)?,
+      "size" => self.size = Vec2::from_lua(value, lua)?,
+      "camera" => {
+        let camera: Option<NodeHandle> = Option::<NodeHandle>::from_lua(value, lua)?;
+        if let Some(cam) = &camera && cam.cast(|_: &mut Camera| ()).is_none() {
+          return Err(mlua::Error::RuntimeError("Viewport camera must be a Camera".to_string()))
+        }
+        self.camera = camera;
+      },
+      "source" => self.source = Option::<NodeHandle>::from_lua(value, lua)?.map(|source| source.downgrade()),
+      "clear_color" => self.clear_color = Option::<Color>::from_lua(value, lua)?,
+      "to_texture" => self.to_texture = bool::from_lua(value, lua)?,
+      _ => return Ok(false)
+    }
+    Ok(true)
+  }
+}
+
+impl Downcastable for Viewport {
+  fn as_any(&mut self) -> &mut dyn std::any::Any {
+    self
+  }
+}
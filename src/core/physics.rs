@@ -0,0 +1,596 @@
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+use mlua::Table;
+
+use crate::core::{broadphase::{CELL_SIZE, SpatialHash}, collision_layers::LayerMask, nodes::{character_body::CharacterBody, collider::Collider, joint::{Joint, JointKind}, rigidbody::{BodyMode, RigidBody}}, proxy::NodeHandle, shape::Hull, vec2::Vec2};
+
+lazy_static! {
+  static ref GRAVITY: Arc<RwLock<Vec2>> = Arc::new(RwLock::new(Vec2::new(0.0, 980.0)));
+  static ref DEBUG_PHYSICS: Arc<RwLock<bool>> = Arc::new(RwLock::new(false));
+}
+
+const SOLVER_ITERATIONS: usize = 4;
+const ONE_WAY_MARGIN: f32 = 1.0;
+//? Slower impacts do not bounce, or resting bodies would hop forever on a restitution above zero.
+const BOUNCE_SPEED: f32 = 40.0;
+//? How far a swept body is let into what it hits, so the solver sees the overlap and bounces it off as usual.
+const SWEEP_OVERLAP: f32 = 1.0;
+
+pub fn load_physics(env: &Table) -> mlua::Result<()> {
+  *GRAVITY.write().unwrap() = env.get::<Option<Vec2>>("Gravity")?.unwrap_or(Vec2::new(0.0, 980.0));
+  *DEBUG_PHYSICS.write().unwrap() = env.get::<Option<bool>>("DebugPhysics")?.unwrap_or(false);
+  Ok(())
+}
+
+pub fn gravity() -> Vec2 {
+  *GRAVITY.read().unwrap()
+}
+
+pub fn debug_physics() -> bool {
+  *DEBUG_PHYSICS.read().unwrap()
+}
+
+//? A body copied out of its node, so the solver runs without holding any lock.
+struct Body {
+  node: NodeHandle,
+  mode: BodyMode,
+  inverse_mass: f32,
+  velocity: Vec2,
+  friction: f32,
+  restitution: f32,
+  moved: Vec2,
+}
+
+struct Fixture {
+  body: Option<usize>,
+  layer: LayerMask,
+  mask: LayerMask,
+  hull: Hull,
+  one_way: Option<Vec2>,
+}
+
+struct Link {
+  node: NodeHandle,
+  kind: JointKind,
+  a: Option<usize>,
+  b: Option<usize>,
+  anchor_a: Vec2,
+  anchor_b: Vec2,
+  length: f32,
+  stiffness: f32,
+  damping: f32,
+}
+
+struct Contact {
+  a: usize,
+  b: usize,
+}
+
+//? Only 'b' coming down onto the top is stopped: it must not be deeper than it travelled towards 'a' since the last check.
+fn one_way_mtv(a: &Hull, up: Vec2, b: &Hull, travel: Vec2) -> Option<(Vec2, f32)> {
+  a.penetration(b)?;
+  let depth: f32 = a.project(up).1 - b.project(up).0;
+  (depth > 0.0 && depth <= (-travel.dot(up)).max(0.0) + ONE_WAY_MARGIN).then_some((up, depth))
+}
+
+pub fn separation(a: &Hull, a_one_way: Option<Vec2>, b: &Hull, b_one_way: Option<Vec2>, travel: Vec2) -> Option<(Vec2, f32)> {
+  match (a_one_way, b_one_way) {
+    (Some(up), _) => one_way_mtv(a, up, b, travel),
+    (None, Some(up)) => one_way_mtv(b, up, a, -travel).map(|(normal, depth)| (-normal, depth)),
+    (None, None) => a.penetration(b)
+  }
+}
+
+pub fn owning_body(node: &NodeHandle) -> Option<NodeHandle> {
+  let mut current: Option<NodeHandle> = node.parent();
+  while let Some(node) = current {
+    if node.cast(|_: &mut RigidBody| ()).is_some() || node.cast(|_: &mut CharacterBody| ()).is_some() {
+      return Some(node)
+    }
+    current = node.parent();
+  }
+  None
+}
+
+struct World {
+  bodies: Vec<Body>,
+  shapes: Vec<Fixture>,
+  links: Vec<Link>,
+}
+
+impl World {
+  fn gather(root: &NodeHandle) -> World {
+    let mut bodies: Vec<Body> = Vec::new();
+    let mut colliders: Vec<NodeHandle> = Vec::new();
+    let mut joints: Vec<NodeHandle> = Vec::new();
+    for node in root.descendants() {
+      let body: Option<Body> = node.cast(|body: &mut RigidBody| Body {
+        node: node.clone(),
+        mode: body.mode,
+        inverse_mass: body.inverse_mass(),
+        velocity: body.velocity,
+        friction: body.friction,
+        restitution: body.restitution,
+        moved: Vec2::ZERO,
+      });
+      //? Characters move themselves before this step, so here they only push like a kinematic body that stays put.
+      let body: Option<Body> = body.or_else(|| node.cast(|character: &mut CharacterBody| Body {
+        node: node.clone(),
+        mode: BodyMode::Static,
+        inverse_mass: 0.0,
+        velocity: character.velocity,
+        friction: 1.0,
+        restitution: 0.0,
+        moved: Vec2::ZERO,
+      }));
+      match body {
+        Some(body) => bodies.push(body),
+        None if node.cast(|_: &mut Collider| ()).is_some() => colliders.push(node),
+        None if node.cast(|_: &mut Joint| ()).is_some() => joints.push(node),
+        None => ()
+      }
+    }
+    let shapes: Vec<Fixture> = colliders.iter().filter_map(|collider| {
+      let body: Option<usize> = owning_body(collider).and_then(|owner| bodies.iter().position(|body| body.node.same(&owner)));
+      collider.cast(|coll: &mut Collider| Fixture { body, layer: coll.layer, mask: coll.mask, hull: coll.hull(), one_way: coll.one_way_up() })
+    }).collect();
+    let links: Vec<Link> = joints.into_iter().filter_map(|joint| World::link(&bodies, joint)).collect();
+    World { bodies, shapes, links }
+  }
+
+  fn link(bodies: &[Body], joint: NodeHandle) -> Option<Link> {
+    let (kind, body_a, body_b, anchor_a, anchor_b, length, stiffness, damping) = joint.cast(|joint: &mut Joint| {
+      (joint.kind, joint.body_a.clone(), joint.body_b.clone(), joint.anchor_a, joint.anchor_b, joint.length, joint.stiffness, joint.damping)
+    })?;
+    //? A joint whose body was freed is skipped, not pinned to the world where that body used to be.
+    let body_a: NodeHandle = body_a?.upgrade()?;
+    let body_b: Option<NodeHandle> = match body_b {
+      Some(body) => Some(body.upgrade()?),
+      None => None
+    };
+    let place = |node: &NodeHandle, anchor: Vec2| -> (Option<usize>, Vec2) {
+      let frame = node.with(|node| node.get_transform().map(|transform| transform.child_frame()));
+      (bodies.iter().position(|body| body.node.same(node)), frame.map_or(anchor, |frame| frame.to_global(anchor)))
+    };
+    let (a, anchor_a) = place(&body_a, anchor_a);
+    let (b, anchor_b) = match body_b {
+      Some(body_b) => place(&body_b, anchor_b),
+      None => (None, anchor_b)
+    };
+    let length: f32 = length.unwrap_or_else(|| {
+      let measured: f32 = (anchor_b - anchor_a).length();
+      joint.cast(|joint: &mut Joint| joint.length = Some(measured));
+      measured
+    });
+    Some(Link { node: joint, kind, a, b, anchor_a, anchor_b, length, stiffness, damping })
+  }
+
+  fn body_inverse_mass(&self, body: Option<usize>) -> f32 {
+    body.map_or(0.0, |body| self.bodies[body].inverse_mass)
+  }
+
+  fn body_velocity(&self, body: Option<usize>) -> Vec2 {
+    body.map_or(Vec2::ZERO, |body| self.bodies[body].velocity)
+  }
+
+  fn ends(&self, link: &Link) -> (Vec2, Vec2) {
+    let moved = |body: Option<usize>| body.map_or(Vec2::ZERO, |body| self.bodies[body].moved);
+    (link.anchor_a + moved(link.a), link.anchor_b + moved(link.b))
+  }
+
+  fn springs(&mut self, deltatime: f32) {
+    for index in 0..self.links.len() {
+      let link: &Link = &self.links[index];
+      let (from, to) = (link.anchor_a, link.anchor_b);
+      if link.kind != JointKind::Spring || from == to {
+        continue
+      }
+      let normal: Vec2 = (to - from).normalized();
+      let closing: f32 = (self.body_velocity(link.b) - self.body_velocity(link.a)).dot(normal);
+      let force: Vec2 = normal * (link.stiffness * ((to - from).length() - link.length) + link.damping * closing);
+      let (a, b, inv_a, inv_b) = (link.a, link.b, self.body_inverse_mass(link.a), self.body_inverse_mass(link.b));
+      if let Some(a) = a {
+        self.bodies[a].velocity += force * inv_a * deltatime;
+      }
+      if let Some(b) = b {
+        self.bodies[b].velocity -= force * inv_b * deltatime;
+      }
+    }
+  }
+
+  fn constrain(&mut self) {
+    for index in 0..self.links.len() {
+      let link: &Link = &self.links[index];
+      let (inv_a, inv_b) = (self.body_inverse_mass(link.a), self.body_inverse_mass(link.b));
+      let total: f32 = inv_a + inv_b;
+      let (from, to) = self.ends(link);
+      let relative: Vec2 = self.body_velocity(link.b) - self.body_velocity(link.a);
+      let (error, impulse): (Vec2, Vec2) = match link.kind {
+        JointKind::Spring => continue,
+        JointKind::Pin => (to - from, relative),
+        JointKind::Distance | JointKind::Rope => {
+          let normal: Vec2 = (to - from).normalized();
+          let stretch: f32 = (to - from).length() - link.length;
+          let apart: f32 = relative.dot(normal);
+          if normal == Vec2::ZERO || (link.kind == JointKind::Rope && stretch <= 0.0) {
+            continue
+          }
+          (normal * stretch, if link.kind == JointKind::Rope && apart < 0.0 { Vec2::ZERO } else { normal * apart })
+        },
+      };
+      if total <= 0.0 {
+        continue
+      }
+      let (a, b) = (link.a, link.b);
+      if let Some(a) = a {
+        self.bodies[a].moved += error * (inv_a / total);
+        self.bodies[a].velocity += impulse * (inv_a / total);
+      }
+      if let Some(b) = b {
+        self.bodies[b].moved -= error * (inv_b / total);
+        self.bodies[b].velocity -= impulse * (inv_b / total);
+      }
+    }
+  }
+
+  fn integrate(&mut self, deltatime: f32) {
+    let gravity: Vec2 = gravity();
+    for body in self.bodies.iter_mut() {
+      if body.mode == BodyMode::Static {
+        continue
+      }
+      if body.mode == BodyMode::Dynamic {
+        let (gravity_scale, force): (f32, Vec2) = body.node.cast(|rb: &mut RigidBody| (rb.gravity_scale, rb.force)).unwrap_or((1.0, Vec2::ZERO));
+        body.velocity += (gravity * gravity_scale + force * body.inverse_mass) * deltatime;
+      }
+      body.moved = body.velocity * deltatime;
+    }
+  }
+
+  //? Dynamic bodies moving more than half their thinnest collider in one step could pass through thin walls, so they are swept instead.
+  fn sweep(&mut self) {
+    for index in 0..self.bodies.len() {
+      let body: &Body = &self.bodies[index];
+      let own: Vec<&Fixture> = self.shapes.iter().filter(|shape| shape.body == Some(index)).collect();
+      let thinnest: f32 = own.iter().map(|shape| shape.hull.thickness()).fold(f32::INFINITY, f32::min);
+      let length: f32 = body.moved.length();
+      if body.mode != BodyMode::Dynamic || length <= thinnest / 2.0 {
+        continue
+      }
+      let mut first: Option<f32> = None;
+      for shape in own.iter() {
+        for other in self.shapes.iter() {
+          if other.body == Some(index) || self.inverse_mass(other) > 0.0 || !LayerMask::pair(shape.layer, shape.mask, other.layer, other.mask) {
+            continue
+          }
+          let motion: Vec2 = body.moved - self.moved(other);
+          let Some((time, normal)) = shape.hull.sweep(motion, &other.hull) else {
+            continue
+          };
+          if other.one_way.is_some_and(|up| time == 0.0 || normal.dot(up) <= 0.0) {
+            continue
+          }
+          if first.is_none_or(|first| time < first) {
+            first = Some(time);
+          }
+        }
+      }
+      if let Some(time) = first {
+        let body: &mut Body = &mut self.bodies[index];
+        body.moved = body.moved.normalized() * (length * time + SWEEP_OVERLAP).min(length);
+      }
+    }
+  }
+
+  fn moved(&self, shape: &Fixture) -> Vec2 {
+    shape.body.map_or(Vec2::ZERO, |body| self.bodies[body].moved)
+  }
+
+  fn hull(&self, shape: &Fixture) -> Hull {
+    shape.hull.translated(self.moved(shape))
+  }
+
+  fn overlap(&self, a: &Fixture, b: &Fixture) -> Option<(Vec2, f32)> {
+    separation(&self.hull(a), a.one_way, &self.hull(b), b.one_way, self.moved(b) - self.moved(a))
+  }
+
+  fn inverse_mass(&self, shape: &Fixture) -> f32 {
+    shape.body.map_or(0.0, |body| self.bodies[body].inverse_mass)
+  }
+
+  fn contacts(&self) -> Vec<Contact> {
+    let hash: SpatialHash = SpatialHash::build(self.shapes.iter().map(|shape| self.hull(shape).aabb()), CELL_SIZE);
+    let mut contacts: Vec<Contact> = Vec::new();
+    for (a, b) in hash.pairs() {
+      let (shape_a, shape_b) = (&self.shapes[a], &self.shapes[b]);
+      if !LayerMask::pair(shape_a.layer, shape_a.mask, shape_b.layer, shape_b.mask) || (shape_a.body.is_some() && shape_a.body == shape_b.body) {
+        continue
+      }
+      if self.inverse_mass(shape_a) + self.inverse_mass(shape_b) <= 0.0 {
+        continue
+      }
+      if self.overlap(shape_a, shape_b).is_some() {
+        contacts.push(Contact { a, b });
+      }
+    }
+    contacts
+  }
+
+  fn velocity(&self, shape: &Fixture) -> Vec2 {
+    shape.body.map_or(Vec2::ZERO, |body| self.bodies[body].velocity)
+  }
+
+  //? Earlier contacts of the same pass may have moved the bodies already, so the overlap is measured again first.
+  fn resolve(&mut self, contact: &Contact) {
+    let (shape_a, shape_b) = (&self.shapes[contact.a], &self.shapes[contact.b]);
+    let Some((normal, depth)) = self.overlap(shape_a, shape_b) else {
+      return
+    };
+    let (body_a, body_b) = (shape_a.body, shape_b.body);
+    let (inv_a, inv_b) = (self.inverse_mass(shape_a), self.inverse_mass(shape_b));
+    let total: f32 = inv_a + inv_b;
+    let relative: Vec2 = self.velocity(shape_b) - self.velocity(shape_a);
+    let material = |body: Option<usize>| body.map_or((0.0, 1.0), |body| (self.bodies[body].restitution, self.bodies[body].friction));
+    let ((rest_a, fric_a), (rest_b, fric_b)) = (material(body_a), material(body_b));
+
+    let push: Vec2 = normal * (depth / total);
+    let closing: f32 = relative.dot(normal);
+    let mut impulse: Vec2 = Vec2::ZERO;
+    if closing < 0.0 {
+      let restitution: f32 = if -closing > BOUNCE_SPEED { rest_a.max(rest_b) } else { 0.0 };
+      let normal_impulse: f32 = -(1.0 + restitution) * closing / total;
+      impulse = normal * normal_impulse;
+      let tangent: Vec2 = (relative - normal * closing).normalized();
+      let limit: f32 = normal_impulse * (fric_a * fric_b).sqrt();
+      impulse += tangent * (-relative.dot(tangent) / total).clamp(-limit, limit);
+    }
+
+    if let Some(body) = body_a {
+      self.bodies[body].moved -= push * inv_a;
+      self.bodies[body].velocity -= impulse * inv_a;
+    }
+    if let Some(body) = body_b {
+      self.bodies[body].moved += push * inv_b;
+      self.bodies[body].velocity += impulse * inv_b;
+    }
+  }
+
+  fn write_back(&self) {
+    for body in self.bodies.iter() {
+      body.node.cast(|rb: &mut RigidBody| {
+        let global: Vec2 = rb.transform.global_pos() + body.moved;
+        rb.transform.pos = rb.transform.parent.to_local(global);
+        rb.velocity = body.velocity;
+        rb.force = Vec2::ZERO;
+      });
+    }
+    for link in self.links.iter() {
+      let (from, to) = self.ends(link);
+      link.node.cast(|joint: &mut Joint| joint.set_ends(from, to));
+    }
+  }
+}
+
+pub struct Overlap {
+  pub a: NodeHandle,
+  pub b: NodeHandle,
+  pub normal: Vec2,
+  pub point: Vec2,
+}
+
+struct Sensed {
+  node: NodeHandle,
+  body: Option<NodeHandle>,
+  layer: LayerMask,
+  mask: LayerMask,
+  hull: Hull,
+}
+
+pub fn overlaps(root: &NodeHandle) -> Vec<Overlap> {
+  let colliders: Vec<Sensed> = root.descendants().into_iter()
+    .filter_map(|node| {
+      let (layer, mask, hull) = node.cast(|coll: &mut Collider| (coll.layer, coll.mask, coll.hull()))?;
+      Some(Sensed { body: owning_body(&node), node, layer, mask, hull })
+    })
+    .collect();
+  let hash: SpatialHash = SpatialHash::build(colliders.iter().map(|coll| coll.hull.aabb()), CELL_SIZE);
+  let mut overlaps: Vec<Overlap> = Vec::new();
+  for (a, b) in hash.pairs() {
+    let (a, b) = (&colliders[a], &colliders[b]);
+    if !LayerMask::pair(a.layer, a.mask, b.layer, b.mask) || a.body.as_ref().zip(b.body.as_ref()).is_some_and(|(body_a, body_b)| body_a.same(body_b)) {
+      continue
+    }
+    if let Some((normal, _)) = a.hull.sat(&b.hull) {
+      overlaps.push(Overlap { a: a.node.clone(), b: b.node.clone(), normal, point: a.hull.contact_point(&b.hull, normal) });
+    }
+  }
+  overlaps
+}
+
+pub fn step(root: &NodeHandle, deltatime: f32) {
+  let mut world: World = World::gather(root);
+  if world.bodies.is_empty() {
+    return
+  }
+  world.springs(deltatime);
+  world.integrate(deltatime);
+  world.sweep();
+  for _ in 0..SOLVER_ITERATIONS {
+    world.constrain();
+    let contacts: Vec<Contact> = world.contacts();
+    if contacts.is_empty() && world.links.is_empty() {
+      break
+    }
+    for contact in contacts.iter() {
+      world.resolve(contact);
+    }
+  }
+  world.write_back();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::core::{nodes::node::Node, proxy::take_graveyard, testing};
+
+  fn body(root: &NodeHandle, name: &str, pos: Vec2, size: Vec2, mode: BodyMode) -> NodeHandle {
+    let body: NodeHandle = NodeHandle::new(RigidBody::new(pos, mode));
+    body.add_child("shape".to_string(), NodeHandle::new(Collider::new(Vec2::ZERO, size, LayerMask(1), LayerMask::EVERYTHING)));
+    root.add_child(name.to_string(), body.clone());
+    body
+  }
+
+  fn floor(root: &NodeHandle) {
+    root.add_child("floor".to_string(), NodeHandle::new(Collider::new(Vec2::new(-1000.0, 100.0), Vec2::new(2000.0, 50.0), LayerMask(1), LayerMask::EVERYTHING)));
+  }
+
+  fn run(root: &NodeHandle, deltatime: f32, steps: usize) {
+    for _ in 0..steps {
+      for node in root.descendants() {
+        node.global_frame();
+      }
+      step(root, deltatime);
+    }
+  }
+
+  fn state(body: &NodeHandle) -> (Vec2, Vec2) {
+    body.cast(|rb: &mut RigidBody| (rb.transform.pos, rb.velocity)).expect("Not a RigidBody")
+  }
+
+  fn assert_near(actual: Vec2, expected: Vec2) {
+    assert!((actual - expected).length() < 1e-3, "{:?} is not {:?}", actual, expected);
+  }
+
+  fn world(gravity: Vec2) -> NodeHandle {
+    *GRAVITY.write().unwrap() = gravity;
+    NodeHandle::new(Node::new())
+  }
+
+  #[test]
+  fn gravity_accelerates_dynamic_bodies_by_their_scale() {
+    let _serial = testing::serial();
+    let root: NodeHandle = world(Vec2::new(0.0, 100.0));
+    let full: NodeHandle = body(&root, "full", Vec2::ZERO, Vec2::new(10.0, 10.0), BodyMode::Dynamic);
+    let half: NodeHandle = body(&root, "half", Vec2::new(100.0, 0.0), Vec2::new(10.0, 10.0), BodyMode::Dynamic);
+    half.cast(|rb: &mut RigidBody| rb.gravity_scale = 0.5);
+    run(&root, 0.1, 2);
+    assert_near(state(&full).0, Vec2::new(0.0, 3.0));
+    assert_near(state(&full).1, Vec2::new(0.0, 20.0));
+    assert_near(state(&half).0, Vec2::new(100.0, 1.5));
+    assert_near(state(&half).1, Vec2::new(0.0, 10.0));
+  }
+
+  #[test]
+  fn restitution_bounces_fast_impacts_back() {
+    let _serial = testing::serial();
+    for (restitution, bounced) in [(1.0, -200.0), (0.0, 0.0)] {
+      let root: NodeHandle = world(Vec2::ZERO);
+      floor(&root);
+      let ball: NodeHandle = body(&root, "ball", Vec2::new(0.0, 55.0), Vec2::new(40.0, 40.0), BodyMode::Dynamic);
+      ball.cast(|rb: &mut RigidBody| (rb.restitution, rb.velocity) = (restitution, Vec2::new(0.0, 200.0)));
+      run(&root, 0.05, 1);
+      assert_near(state(&ball).0, Vec2::new(0.0, 60.0));
+      assert_near(state(&ball).1, Vec2::new(0.0, bounced));
+    }
+  }
+
+  #[test]
+  fn friction_slows_bodies_sliding_on_the_floor() {
+    let _serial = testing::serial();
+    for (friction, speed) in [(0.2, 100.0 - 10.0 * 0.2f32.sqrt()), (0.0, 100.0)] {
+      let root: NodeHandle = world(Vec2::new(0.0, 100.0));
+      floor(&root);
+      let crate_body: NodeHandle = body(&root, "crate", Vec2::new(0.0, 60.0), Vec2::new(40.0, 40.0), BodyMode::Dynamic);
+      crate_body.cast(|rb: &mut RigidBody| (rb.friction, rb.velocity) = (friction, Vec2::new(100.0, 0.0)));
+      run(&root, 0.1, 1);
+      assert_near(state(&crate_body).0, Vec2::new(10.0, 60.0));
+      assert_near(state(&crate_body).1, Vec2::new(speed, 0.0));
+    }
+  }
+
+  #[test]
+  fn static_bodies_stay_and_kinematic_ones_push_without_being_pushed() {
+    let _serial = testing::serial();
+    let root: NodeHandle = world(Vec2::new(0.0, 100.0));
+    let wall: NodeHandle = body(&root, "wall", Vec2::new(0.0, 200.0), Vec2::new(40.0, 40.0), BodyMode::Static);
+    wall.cast(|rb: &mut RigidBody| rb.velocity = Vec2::new(50.0, 0.0));
+    let pusher: NodeHandle = body(&root, "pusher", Vec2::ZERO, Vec2::new(40.0, 40.0), BodyMode::Kinematic);
+    pusher.cast(|rb: &mut RigidBody| rb.velocity = Vec2::new(100.0, 0.0));
+    let pushed: NodeHandle = body(&root, "pushed", Vec2::new(45.0, 0.0), Vec2::new(40.0, 40.0), BodyMode::Dynamic);
+    pushed.cast(|rb: &mut RigidBody| rb.gravity_scale = 0.0);
+    run(&root, 0.1, 1);
+    assert_near(state(&wall).0, Vec2::new(0.0, 200.0));
+    assert_near(state(&pusher).0, Vec2::new(10.0, 0.0));
+    assert_near(state(&pusher).1, Vec2::new(100.0, 0.0));
+    assert_near(state(&pushed).0, Vec2::new(50.0, 0.0));
+    assert_near(state(&pushed).1, Vec2::new(100.0, 0.0));
+  }
+
+  fn joint(root: &NodeHandle, kind: JointKind, body_a: &NodeHandle, body_b: &NodeHandle) -> NodeHandle {
+    let joint: NodeHandle = NodeHandle::new(Joint::new(kind, body_a, Some(body_b)));
+    root.add_child(format!("{}-joint", kind.name()), joint.clone());
+    joint
+  }
+
+  fn distance(a: &NodeHandle, b: &NodeHandle) -> f32 {
+    (state(b).0 - state(a).0).length()
+  }
+
+  #[test]
+  fn distance_and_pin_joints_hold_a_swinging_body() {
+    let _serial = testing::serial();
+    let root: NodeHandle = world(Vec2::new(0.0, 100.0));
+    let anchor: NodeHandle = body(&root, "anchor", Vec2::ZERO, Vec2::new(10.0, 10.0), BodyMode::Static);
+    let swinging: NodeHandle = body(&root, "swinging", Vec2::new(100.0, 0.0), Vec2::new(10.0, 10.0), BodyMode::Dynamic);
+    let pinned: NodeHandle = body(&root, "pinned", Vec2::new(-100.0, 0.0), Vec2::new(10.0, 10.0), BodyMode::Dynamic);
+    joint(&root, JointKind::Distance, &anchor, &swinging);
+    joint(&root, JointKind::Pin, &anchor, &pinned).cast(|joint: &mut Joint| joint.anchor_a = Vec2::new(-100.0, 0.0));
+    for _ in 0..120 {
+      run(&root, 1.0 / 60.0, 1);
+      assert!((distance(&anchor, &swinging) - 100.0).abs() < 1.0, "The distance joint stretched to {}", distance(&anchor, &swinging));
+      assert!((state(&pinned).0 - Vec2::new(-100.0, 0.0)).length() < 1.0, "The pin joint let go at {:?}", state(&pinned).0);
+    }
+    assert!(state(&swinging).0.get_y() > 50.0);
+  }
+
+  #[test]
+  fn springs_settle_at_their_length_and_ropes_only_pull() {
+    let _serial = testing::serial();
+    let root: NodeHandle = world(Vec2::ZERO);
+    let anchor: NodeHandle = body(&root, "anchor", Vec2::ZERO, Vec2::new(10.0, 10.0), BodyMode::Static);
+    let bouncing: NodeHandle = body(&root, "bouncing", Vec2::new(150.0, 0.0), Vec2::new(10.0, 10.0), BodyMode::Dynamic);
+    joint(&root, JointKind::Spring, &anchor, &bouncing).cast(|joint: &mut Joint| joint.length = Some(100.0));
+    run(&root, 1.0 / 60.0, 600);
+    assert!((distance(&anchor, &bouncing) - 100.0).abs() < 1.0, "The spring settled at {}", distance(&anchor, &bouncing));
+
+    let root: NodeHandle = world(Vec2::new(0.0, 100.0));
+    let anchor: NodeHandle = body(&root, "anchor", Vec2::ZERO, Vec2::new(10.0, 10.0), BodyMode::Static);
+    let hanging: NodeHandle = body(&root, "hanging", Vec2::new(0.0, 50.0), Vec2::new(10.0, 10.0), BodyMode::Dynamic);
+    joint(&root, JointKind::Rope, &anchor, &hanging).cast(|joint: &mut Joint| joint.length = Some(100.0));
+    run(&root, 0.1, 3);
+    assert_near(state(&hanging).0, Vec2::new(0.0, 56.0));
+    for _ in 0..60 {
+      run(&root, 1.0 / 60.0, 1);
+      assert!(distance(&anchor, &hanging) < 101.0, "The rope stretched to {}", distance(&anchor, &hanging));
+    }
+    assert!((distance(&anchor, &hanging) - 100.0).abs() < 1.0);
+  }
+
+  #[test]
+  fn joints_with_a_freed_body_are_skipped() {
+    let _serial = testing::serial();
+    for freed_first in [true, false] {
+      let root: NodeHandle = world(Vec2::new(0.0, 100.0));
+      let gone: NodeHandle = body(&root, "gone", Vec2::ZERO, Vec2::new(10.0, 10.0), BodyMode::Static);
+      let falling: NodeHandle = body(&root, "falling", Vec2::new(100.0, 0.0), Vec2::new(10.0, 10.0), BodyMode::Dynamic);
+      let joint: NodeHandle = if freed_first { joint(&root, JointKind::Pin, &gone, &falling) } else { joint(&root, JointKind::Pin, &falling, &gone) };
+      root.with(|root| root.get_base().remove_child("gone"));
+      take_graveyard();
+      drop(gone);
+      assert!(joint.cast(|joint: &mut Joint| joint.body_a.iter().chain(&joint.body_b).any(|body| body.upgrade().is_none())).unwrap());
+      run(&root, 0.1, 1);
+      assert_near(state(&falling).0, Vec2::new(100.0, 1.0));
+    }
+  }
+}
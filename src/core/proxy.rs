@@ -0,0 +1,477 @@
+use std::{any::Any, sync::{Arc, Mutex, RwLock, Weak}};
+
+use lazy_static::lazy_static;
+use mlua::{FromLua, FromLuaMulti, Function, IntoLua, IntoLuaMulti, Lua, MetaMethod, MultiValue, Table, UserData, UserDataMethods, Value};
+
+use crate::core::{color::Color, image::Img, nodelike::NodeLike, nodes::collider::Collider, transform::{ParentFrame, Transform}, vec2::Vec2};
+
+pub type DynNode = dyn NodeLike + Send + Sync + 'static;
+pub type SharedNode = Arc<RwLock<Box<DynNode>>>;
+type NodePath = Arc<dyn for<'a> Fn(&'a mut DynNode) -> &'a mut DynNode + Send + Sync>;
+type Accessor<T> = Arc<dyn for<'a> Fn(&'a mut dyn Any) -> &'a mut T + Send + Sync>;
+
+lazy_static! {
+  static ref GRAVEYARD: Arc<Mutex<Vec<NodeHandle>>> = Arc::new(Mutex::new(Vec::new()));
+}
+
+fn node_path<F>(func: F) -> NodePath where F: for<'a> Fn(&'a mut DynNode) -> &'a mut DynNode + Send + Sync + 'static {
+  Arc::new(func)
+}
+
+fn accessor<T, F>(func: F) -> Accessor<T> where F: for<'a> Fn(&'a mut dyn Any) -> &'a mut T + Send + Sync + 'static {
+  Arc::new(func)
+}
+
+pub fn bury(node: NodeHandle) {
+  GRAVEYARD.lock().unwrap().push(node);
+}
+
+pub fn take_graveyard() -> Vec<NodeHandle> {
+  std::mem::take(&mut *GRAVEYARD.lock().unwrap())
+}
+
+pub fn method<A, R, F>(lua: &Lua, name: &str, func: F) -> mlua::Result<Value> where A: FromLuaMulti, R: IntoLuaMulti, F: Fn(&Lua, A) -> mlua::Result<R> + Send + 'static {
+  if let Some(cached) = lua.named_registry_value::<Option<Function>>(name)? {
+    return Ok(Value::Function(cached))
+  }
+  let created: Function = lua.create_function(func)?;
+  lua.set_named_registry_value(name, &created)?;
+  Ok(Value::Function(created))
+}
+
+#[derive(Clone)]
+pub struct NodeHandle {
+  node: SharedNode,
+  path: Option<NodePath>,
+}
+
+#[derive(Clone)]
+pub struct WeakNodeHandle {
+  node: Weak<RwLock<Box<DynNode>>>,
+  path: Option<NodePath>,
+}
+
+impl WeakNodeHandle {
+  pub fn upgrade(&self) -> Option<NodeHandle> {
+    Some(NodeHandle { node: self.node.upgrade()?, path: self.path.clone() })
+  }
+}
+
+impl NodeHandle {
+  pub fn new<N: NodeLike + 'static>(node: N) -> NodeHandle {
+    NodeHandle { node: Arc::new(RwLock::new(Box::new(node))), path: None }
+  }
+
+  pub fn with<R>(&self, func: impl FnOnce(&mut DynNode) -> R) -> R {
+    let mut guard = self.node.write().unwrap();
+    match &self.path {
+      Some(path) => func(path(guard.as_mut())),
+      None => func(guard.as_mut()),
+    }
+  }
+
+  pub fn cast<N: 'static, R>(&self, func: impl FnOnce(&mut N) -> R) -> Option<R> {
+    self.with(|node| node.as_any().downcast_mut::<N>().map(func))
+  }
+
+  pub fn sub<N: 'static, S: NodeLike + 'static>(&self, access: fn(&mut N) -> &mut S) -> NodeHandle {
+    let parent: Option<NodePath> = self.path.clone();
+    NodeHandle {
+      node: self.node.clone(),
+      path: Some(node_path(move |node| {
+        let node: &mut DynNode = match &parent {
+          Some(path) => path(node),
+          None => node
+        };
+        access(node.as_any().downcast_mut::<N>().expect("Invalid sub node"))
+      }))
+    }
+  }
+
+  pub fn downgrade(&self) -> WeakNodeHandle {
+    WeakNodeHandle { node: Arc::downgrade(&self.node), path: self.path.clone() }
+  }
+
+  pub fn parent(&self) -> Option<NodeHandle> {
+    let owner: NodeHandle = NodeHandle { node: self.node.clone(), path: None };
+    owner.with(|node| node.get_base().parent.clone())?.upgrade()
+  }
+
+  pub fn add_child(&self, name: String, child: NodeHandle) {
+    child.with(|node| node.get_base().parent = Some(self.downgrade()));
+    Collider::moved(&child);
+    self.with(|node| node.get_base().add_child(name, child));
+  }
+
+  //? Locks the ancestors one at a time, so it must not run while any of them is locked.
+  pub fn global_frame(&self) -> ParentFrame {
+    let parent: ParentFrame = match self.parent() {
+      Some(parent) => parent.global_frame(),
+      None => ParentFrame::IDENTITY
+    };
+    self.with(|node| node.propagate(parent))
+  }
+
+  pub fn id(&self) -> u64 {
+    self.with(|node| node.get_id())
+  }
+
+  pub fn kind(&self) -> String {
+    self.with(|node| node.get_kind().to_string())
+  }
+
+  pub fn same(&self, other: &NodeHandle) -> bool {
+    Arc::ptr_eq(&self.node, &other.node) && self.id() == other.id()
+  }
+
+  pub fn children(&self) -> Vec<(String, NodeHandle)> {
+    self.with(|node| node.get_base().children.entries())
+  }
+
+  pub fn get_child(&self, name: &str) -> Option<NodeHandle> {
+    self.with(|node| node.get_base().children.get_mut(&name.to_string()).cloned())
+  }
+
+  pub fn descendants(&self) -> Vec<NodeHandle> {
+    let mut ret: Vec<NodeHandle> = vec![self.clone()];
+    for (_, child) in self.children() {
+      ret.extend(child.descendants());
+    }
+    ret
+  }
+
+  pub fn describe(&self, lua: &Lua, depth: usize) -> mlua::Result<String> {
+    let indent: String = "\t".repeat(depth);
+    let (keys, extras): (Vec<&'static str>, Option<Table>) = self.with(|node| (node.lua_keys(), node.get_base().extras()));
+    let mut ret: String = format!("{}<{}> {{\n", self.kind(), self.id());
+    for key in keys {
+      let value: Value = self.with(|node| node.lua_get(lua, self, key))?;
+      let shown: String = match NodeHandle::from_lua(value.clone(), lua) {
+        Ok(node) => node.describe(lua, depth + 1)?,
+        Err(_) => show_value(&value)?
+      };
+      ret.push_str(&format!("{}\t{}: {}\n", indent, key, shown));
+    }
+    if let Some(extras) = extras {
+      extras.for_each(|key: String, value: Value| {
+        ret.push_str(&format!("{}\t{}: {}\n", indent, key, value.to_string()?));
+        Ok(())
+      })?;
+    }
+    let children: Vec<(String, NodeHandle)> = self.children();
+    if !children.is_empty() {
+      ret.push_str(&format!("{}\tchildren: {{\n", indent));
+      for (name, child) in children {
+        ret.push_str(&format!("{}\t\t{}: {}\n", indent, name, child.describe(lua, depth + 2)?));
+      }
+      ret.push_str(&format!("{}\t}}\n", indent));
+    }
+    ret.push_str(&format!("{}}}", indent));
+    Ok(ret)
+  }
+}
+
+fn show_value(value: &Value) -> mlua::Result<String> {
+  match value {
+    Value::Table(tbl) => {
+      let mut parts: Vec<String> = Vec::new();
+      for pair in tbl.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        parts.push(match key {
+          Value::Integer(_) => show_value(&value)?,
+          key => format!("{} = {}", key.to_string()?, show_value(&value)?)
+        });
+      }
+      Ok(format!("{{{}}}", parts.join(", ")))
+    },
+    other => other.to_string()
+  }
+}
+
+impl FromLua for NodeHandle {
+  fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+    match value {
+      Value::UserData(ud) if ud.is::<NodeHandle>() => Ok(ud.borrow::<NodeHandle>()?.clone()),
+      _ => Err(mlua::Error::FromLuaConversionError { from: value.type_name(), to: "Node".to_string(), message: None })
+    }
+  }
+}
+
+impl UserData for NodeHandle {
+  fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+    methods.add_meta_method(MetaMethod::Index, |lua, this, key: String| {
+      if key == "global_position" {
+        return this.global_frame().pos.into_lua(lua)
+      }
+      let value: Value = this.with(|node| node.lua_get(lua, this, &key))?;
+      if !value.is_nil() {
+        seal(&value);
+        return Ok(value)
+      }
+      this.with(|node| node.get_base().get_extra(&key))
+    });
+
+    methods.add_meta_method(MetaMethod::NewIndex, |lua, this, (key, value): (String, Value)| {
+      //? Places rooted in this very node must be read before its lock is taken.
+      let value: Value = detach(lua, value, 0)?;
+      if key == "global_position" {
+        let parent: ParentFrame = this.parent().map(|parent| parent.global_frame()).unwrap_or(ParentFrame::IDENTITY);
+        let pos: Vec2 = parent.to_local(Vec2::from_lua(value, lua)?);
+        let moved: bool = this.with(|node| node.get_position().map(|local| *local = pos).is_some());
+        if !moved {
+          return Err(mlua::Error::RuntimeError(format!("{} has no position", this.kind())))
+        }
+        Collider::moved(this);
+        return Ok(())
+      }
+      let handled: bool = this.with(|node| node.lua_set(lua, &key, value.clone()))?;
+      if handled {
+        Collider::moved(this);
+      } else {
+        this.with(|node| node.get_base().set_extra(lua, &key, value))?;
+      }
+      Ok(())
+    });
+
+    methods.add_meta_method(MetaMethod::Eq, |_, this, other: NodeHandle| {
+      Ok(this.same(&other))
+    });
+
+    methods.add_meta_method(MetaMethod::ToString, |lua, this, ()| {
+      this.describe(lua, 0)
+    });
+  }
+}
+
+pub struct ChildrenProxy(pub NodeHandle);
+
+impl UserData for ChildrenProxy {
+  fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+    methods.add_meta_method(MetaMethod::Index, |_, this, name: String| {
+      Ok(this.0.get_child(&name))
+    });
+
+    methods.add_meta_method(MetaMethod::NewIndex, |_, this, (name, child): (String, Option<NodeHandle>)| {
+      match child {
+        Some(child) => this.0.add_child(name, child),
+        None => this.0.with(|node| node.get_base().remove_child(&name)),
+      }
+      Ok(())
+    });
+
+    methods.add_meta_method(MetaMethod::Len, |_, this, ()| {
+      Ok(this.0.with(|node| node.get_base().children.len()))
+    });
+
+    methods.add_meta_method(MetaMethod::Pairs, |lua, this, ()| {
+      let entries: Vec<(String, NodeHandle)> = this.0.children();
+      let mut index: usize = 0;
+      let next: Function = lua.create_function_mut(move |lua, _: MultiValue| {
+        let Some((name, child)) = entries.get(index).cloned() else {
+          return Ok(MultiValue::new())
+        };
+        index += 1;
+        (name, child).into_lua_multi(lua)
+      })?;
+      Ok((next, Value::Nil, Value::Nil))
+    });
+
+    methods.add_meta_method(MetaMethod::ToString, |lua, this, ()| {
+      let mut ret: String = "{\n".to_string();
+      for (name, child) in this.0.children() {
+        ret.push_str(&format!("\t{}: {}\n", name, child.describe(lua, 1)?));
+      }
+      ret.push('}');
+      Ok(ret)
+    });
+  }
+}
+
+enum Root {
+  Node(NodeHandle),
+  Owned(Arc<RwLock<Box<dyn Any + Send + Sync>>>),
+}
+
+//? Empty until the place is sealed, which happens as soon as the node it was read from is unlocked again.
+type Snapshot = Arc<RwLock<Option<Box<dyn Any + Send + Sync>>>>;
+
+//? Reading 'this.transform.pos' hands out a Place into the node, so 'this.transform.pos.x = 3' writes through.
+//? Reads see the value as it was when the field was read, so 'local prev = this.transform.pos' keeps the old position
+//? after the node moves. Places read from the same field share that copy, and writing through any of them updates it.
+pub struct Place<T> {
+  root: Arc<Root>,
+  access: Accessor<T>,
+  snapshot: Option<(Snapshot, Accessor<T>)>,
+}
+
+impl<T> Clone for Place<T> {
+  fn clone(&self) -> Self {
+    Place { root: self.root.clone(), access: self.access.clone(), snapshot: self.snapshot.clone() }
+  }
+}
+
+impl<T: Clone + Send + Sync + 'static> Place<T> {
+  pub fn owned(value: T) -> Place<T> {
+    Place {
+      root: Arc::new(Root::Owned(Arc::new(RwLock::new(Box::new(value))))),
+      access: accessor(|any| any.downcast_mut::<T>().expect("Invalid owned value")),
+      snapshot: None,
+    }
+  }
+
+  pub fn field<N: 'static>(node: &NodeHandle, access: fn(&mut N) -> &mut T) -> Place<T> {
+    Place {
+      root: Arc::new(Root::Node(node.clone())),
+      access: accessor(move |any| access(any.downcast_mut::<N>().expect("Invalid node field"))),
+      snapshot: Some((Arc::new(RwLock::new(None)), accessor(|any| any.downcast_mut::<T>().expect("Invalid snapshot")))),
+    }
+  }
+
+  //? Takes the copy reads will see. Places are made while their node is locked, so this waits until it is not.
+  pub fn seal(&self) {
+    if let Some((snapshot, _)) = &self.snapshot && snapshot.read().unwrap().is_none() {
+      let value: T = self.live(|value| value.clone());
+      *snapshot.write().unwrap() = Some(Box::new(value));
+    }
+  }
+
+  pub fn map<U: Clone + Send + Sync + 'static>(&self, access: fn(&mut T) -> &mut U) -> Place<U> {
+    let parent: Accessor<T> = self.access.clone();
+    let snapshot = self.snapshot.as_ref().map(|(value, parent)| {
+      let parent: Accessor<T> = parent.clone();
+      (value.clone(), accessor(move |any| access(parent(any))))
+    });
+    Place { root: self.root.clone(), access: accessor(move |any| access(parent(any))), snapshot }
+  }
+
+  fn live<R>(&self, func: impl FnOnce(&mut T) -> R) -> R {
+    match self.root.as_ref() {
+      Root::Node(node) => node.with(|node| func((self.access)(node.as_any()))),
+      Root::Owned(value) => {
+        let mut guard = value.write().unwrap();
+        func((self.access)(guard.as_mut()))
+      }
+    }
+  }
+
+  pub fn with<R>(&self, func: impl FnOnce(&mut T) -> R) -> R {
+    let (result, value): (R, T) = self.live(|value| (func(value), value.clone()));
+    if let Root::Node(node) = self.root.as_ref() {
+      Collider::moved(node);
+    }
+    if let Some((snapshot, access)) = &self.snapshot
+      && let Some(copy) = snapshot.write().unwrap().as_mut() {
+      *access(copy.as_mut()) = value;
+    }
+    result
+  }
+
+  pub fn get(&self) -> T {
+    if let Some((snapshot, access)) = &self.snapshot
+      && let Some(copy) = snapshot.write().unwrap().as_mut() {
+      return access(copy.as_mut()).clone()
+    }
+    self.live(|value| value.clone())
+  }
+}
+
+fn seal(value: &Value) {
+  if let Value::UserData(ud) = value {
+    if let Ok(place) = ud.borrow::<Place<Vec2>>() {
+      place.seal();
+    } else if let Ok(place) = ud.borrow::<Place<Transform>>() {
+      place.seal();
+    } else if let Ok(place) = ud.borrow::<Place<Color>>() {
+      place.seal();
+    } else if let Ok(place) = ud.borrow::<Place<Img>>() {
+      place.seal();
+    }
+  }
+}
+
+pub fn detach(lua: &Lua, value: Value, depth: usize) -> mlua::Result<Value> {
+  match value {
+    Value::UserData(ud) => {
+      if let Ok(place) = ud.borrow::<Place<Vec2>>() {
+        return place.get().into_lua(lua)
+      }
+      if let Ok(place) = ud.borrow::<Place<Transform>>() {
+        return place.get().into_lua(lua)
+      }
+      if let Ok(place) = ud.borrow::<Place<Color>>() {
+        return place.get().into_lua(lua)
+      }
+      if let Ok(place) = ud.borrow::<Place<Img>>() {
+        return place.get().into_lua(lua)
+      }
+      Ok(Value::UserData(ud))
+    },
+    Value::Table(tbl) if depth == 0 => {
+      let copy: Table = lua.create_table()?;
+      tbl.for_each(|key: Value, value: Value| {
+        copy.set(key, detach(lua, value, depth + 1)?)
+      })?;
+      Ok(Value::Table(copy))
+    },
+    other => Ok(other)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::core::{nodes::{node::Node, rectmesh::RectMesh}, testing};
+
+  fn mesh(x: f32) -> NodeHandle {
+    NodeHandle::new(RectMesh::new(Vec2::new(x, 0.0), Vec2::new(10.0, 10.0), Color::new(0)))
+  }
+
+  fn pos(node: &NodeHandle) -> Vec2 {
+    node.cast(|mesh: &mut RectMesh| mesh.transform.pos).expect("Not a RectMesh")
+  }
+
+  #[test]
+  fn nested_places_write_through_to_the_node() {
+    let lua: Lua = Lua::new();
+    let node: NodeHandle = mesh(1.0);
+    lua.globals().set("node", node.clone()).unwrap();
+    lua.load("node.transform.pos.x = 5; local size = node.transform.size; size.y = 3").exec().unwrap();
+    assert_eq!(pos(&node), Vec2::new(5.0, 0.0));
+    assert_eq!(node.cast(|mesh: &mut RectMesh| mesh.transform.size), Some(Vec2::new(10.0, 3.0)));
+  }
+
+  #[test]
+  fn sealed_places_keep_the_value_they_were_read_with() {
+    let lua: Lua = Lua::new();
+    let node: NodeHandle = mesh(1.0);
+    lua.globals().set("node", node.clone()).unwrap();
+    lua.load("prev = node.transform.pos").exec().unwrap();
+    node.cast(|mesh: &mut RectMesh| mesh.transform.pos = Vec2::new(9.0, 9.0));
+    lua.load("node.transform.pos.y = 4").exec().unwrap();
+    assert_eq!(lua.load("return prev.x, prev.y").eval::<(f32, f32)>().unwrap(), (1.0, 0.0));
+    assert_eq!(pos(&node), Vec2::new(9.0, 4.0));
+  }
+
+  #[test]
+  fn freed_nodes_are_buried_and_their_names_error() {
+    let _serial = testing::serial();
+    take_graveyard();
+    let lua: Lua = Lua::new();
+    let root: NodeHandle = NodeHandle::new(Node::new());
+    let enemy: NodeHandle = mesh(2.0);
+    root.add_child("enemy".to_string(), enemy.clone());
+    lua.globals().set("root", ChildrenProxy(root.clone())).unwrap();
+
+    let held: f32 = lua.load("local enemy = root.enemy; root.enemy = nil; return enemy.transform.pos.x").eval().unwrap();
+    assert_eq!(held, 2.0);
+    assert!(lua.load("return root.enemy.transform").eval::<Value>().is_err());
+    assert!(enemy.parent().is_none());
+    let buried: Vec<NodeHandle> = take_graveyard();
+    assert!(buried.len() == 1 && buried[0].same(&enemy));
+
+    let weak: WeakNodeHandle = enemy.downgrade();
+    drop((buried, enemy));
+    lua.gc_collect().unwrap();
+    assert!(weak.upgrade().is_none());
+  }
+}
@@ -0,0 +1,103 @@
+use mlua::{IntoLua, Lua, Table, Value};
+
+use crate::core::{broadphase::Aabb, collision_layers::LayerMask, nodes::collider::Collider, proxy::NodeHandle, shape::Hull, vec2::Vec2};
+
+//? A collider found by a raycast, a sweep or a shape query. 'normal' faces out of the collider, toward whatever asked.
+pub struct Hit {
+  pub node: NodeHandle,
+  pub layer: LayerMask,
+  pub point: Vec2,
+  pub normal: Vec2,
+  pub distance: f32,
+  pub time: f32,
+}
+
+impl IntoLua for Hit {
+  fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+    let table: Table = lua.create_table()?;
+    table.set("id", self.node.id())?;
+    table.set("kind", self.node.kind())?;
+    table.set("node", self.node)?;
+    table.set("layer", self.layer)?;
+    table.set("point", self.point)?;
+    table.set("normal", self.normal)?;
+    table.set("distance", self.distance)?;
+    table.set("time", self.time)?;
+    Ok(Value::Table(table))
+  }
+}
+
+fn nearby(bounds: Aabb, mask: LayerMask) -> Vec<(NodeHandle, LayerMask, Hull)> {
+  Collider::candidates(bounds).into_iter()
+    .filter_map(|coll| {
+      coll.global_frame();
+      let (layer, hull) = coll.cast(|coll: &mut Collider| (coll.layer, coll.hull()))?;
+      Some((coll, layer, hull))
+    })
+    .filter(|(_, layer, hull)| mask.intersects(*layer) && hull.aabb().overlaps(bounds))
+    .collect()
+}
+
+pub fn raycast(from: Vec2, to: Vec2, mask: LayerMask) -> Option<Hit> {
+  let (direction, length) = ((to - from).normalized(), (to - from).length());
+  let bounds: Aabb = Aabb { min: from, max: from }.union(Aabb { min: to, max: to });
+  nearby(bounds, mask).into_iter()
+    .filter_map(|(node, layer, hull)| {
+      let (distance, normal) = hull.raycast(from, direction, length)?;
+      Some(Hit { node, layer, point: from + direction * distance, normal, distance, time: if length > 0.0 { distance / length } else { 0.0 } })
+    })
+    .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
+
+pub fn overlapping(area: &Hull, mask: LayerMask) -> Vec<Hit> {
+  let center: Vec2 = area.center();
+  let mut hits: Vec<Hit> = nearby(area.aabb(), mask).into_iter()
+    .filter_map(|(node, layer, hull)| {
+      let (normal, _) = area.sat(&hull)?;
+      let point: Vec2 = hull.nearest_point(center);
+      let outward: Vec2 = (center - point).normalized();
+      let normal: Vec2 = if outward == Vec2::ZERO { -normal } else { outward };
+      Some(Hit { node, layer, point, normal, distance: (center - point).length(), time: 0.0 })
+    })
+    .collect();
+  hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+  hits
+}
+
+pub fn query_point(point: Vec2, mask: LayerMask) -> Vec<Hit> {
+  overlapping(&Hull { points: vec![point], radius: 0.0 }, mask)
+}
+
+fn rect(pos: Vec2, size: Vec2) -> Hull {
+  let (width, height) = (Vec2::new(size.get_x(), 0.0), Vec2::new(0.0, size.get_y()));
+  Hull { points: vec![pos, pos + width, pos + size, pos + height], radius: 0.0 }
+}
+
+pub fn query_rect(pos: Vec2, size: Vec2, mask: LayerMask) -> Vec<Hit> {
+  overlapping(&rect(pos, size), mask)
+}
+
+pub fn query_circle(center: Vec2, radius: f32, mask: LayerMask) -> Vec<Hit> {
+  overlapping(&Hull { points: vec![center], radius }, mask)
+}
+
+pub fn sweep(hull: &Hull, motion: Vec2, mask: LayerMask, skip: impl Fn(&NodeHandle) -> bool) -> Option<Hit> {
+  let bounds: Aabb = hull.aabb();
+  let bounds: Aabb = bounds.union(Aabb { min: bounds.min + motion, max: bounds.max + motion });
+  nearby(bounds, mask).into_iter()
+    .filter(|(node, _, _)| !skip(node))
+    .filter_map(|(node, layer, other)| {
+      let (time, normal) = hull.sweep(motion, &other)?;
+      let point: Vec2 = other.nearest_point(hull.center() + motion * time);
+      Some(Hit { node, layer, point, normal, distance: motion.length() * time, time })
+    })
+    .min_by(|a, b| a.time.total_cmp(&b.time))
+}
+
+pub fn sweep_rect(pos: Vec2, size: Vec2, motion: Vec2, mask: LayerMask) -> Option<Hit> {
+  sweep(&rect(pos, size), motion, mask, |_| false)
+}
+
+pub fn sweep_circle(center: Vec2, radius: f32, motion: Vec2, mask: LayerMask) -> Option<Hit> {
+  sweep(&Hull { points: vec![center], radius }, motion, mask, |_| false)
+}
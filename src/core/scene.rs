@@ -0,0 +1,391 @@
+use std::{collections::{HashMap, HashSet}, error::Error, fs, sync::{Arc, Mutex}};
+
+use lazy_static::lazy_static;
+use macroquad::prelude::warn;
+use mlua::{Chunk, Function, IntoLua, Lua, MultiValue, Table, Value};
+
+use crate::core::{color::Color, core::{init_env_commons, load_persistrent}, layering::DrawKey, engine::{refresh_camera, use_render_camera}, nodes::{canvas_layer::CanvasLayer, collider::Collider, node::Node, viewport::Viewport}, physics::{self, Overlap, owning_body}, proxy::{ChildrenProxy, NodeHandle, take_graveyard}, script_manager::ScriptManager, transform::ParentFrame, vec2::Vec2};
+
+pub enum SceneRequest {
+  Change(String),
+  Push(String),
+  Pop,
+}
+
+lazy_static! {
+  static ref SCENE_REQUESTS: Arc<Mutex<Vec<SceneRequest>>> = Arc::new(Mutex::new(Vec::new()));
+}
+
+//? Scene switches are queued and applied by the engine once the current frame is done with the old tree.
+pub fn request_scene(request: SceneRequest) {
+  SCENE_REQUESTS.lock().unwrap().push(request);
+}
+
+pub fn take_scene_requests() -> Vec<SceneRequest> {
+  std::mem::take(&mut *SCENE_REQUESTS.lock().unwrap())
+}
+
+pub struct Scene {
+  pub path: String,
+  pub bg_color: Option<Color>,
+  root: NodeHandle,
+  environment: Table,
+  ready: bool,
+  contacts: HashMap<(u64, u64), Overlap>,
+  //? The main camera when another scene was pushed over this one, put back when that scene is popped.
+  pub camera: Option<NodeHandle>,
+}
+
+impl Scene {
+  fn init_env(lua: &Lua, env: &Table, root: &NodeHandle) -> Result<(), Box<dyn Error>> {
+    env.set("root", ChildrenProxy(root.clone()))?;
+
+    init_env_commons(lua, env)?;
+
+    let root: NodeHandle = root.clone();
+    env.set("add_node", lua.create_function_mut(move |_, (name, node): (String, NodeHandle)| {
+      root.add_child(name, node.clone());
+      Ok(node)
+    })?)?;
+
+    Ok(())
+  }
+
+  pub fn load(lua: &Lua, path: &str) -> Result<Scene, Box<dyn Error>> {
+    let file_content: String = fs::read_to_string(path)?;
+    let chunk: Chunk = lua.load(file_content).set_name(path);
+    let environment: Table = lua.create_table()?;
+    let root: NodeHandle = NodeHandle::new(Node::new());
+    Scene::init_env(lua, &environment, &root)?;
+    chunk.set_environment(environment.clone()).exec()?;
+
+    let bg_color: Option<Color> = environment.get::<Option<Color>>("color").ok().flatten();
+    Ok( Scene { path: path.to_string(), bg_color, root, environment, ready: false, contacts: HashMap::new(), camera: None } )
+  }
+
+  pub fn environment(&self) -> &Table {
+    &self.environment
+  }
+
+  pub fn root(&self) -> NodeHandle {
+    self.root.clone()
+  }
+
+  pub fn is_ready(&self) -> bool {
+    self.ready
+  }
+
+  fn run_node_scripts(lua: &Lua, node: &NodeHandle, func_name: &str, args: MultiValue) {
+    let envs: Vec<Table> = node.with(|node| node.get_scripts().environments());
+    if let Err(err) = ScriptManager::run_4all_envs(lua, &envs, func_name, args) {
+      warn!("Error during {} in script", func_name);
+      eprintln!("ERROR: {}", err);
+    }
+  }
+
+  fn start_nodes(&mut self, lua: &Lua) {
+    for node in self.root.descendants() {
+      let started: bool = node.with(|node| {
+        let started: bool = node.get_scripts().is_started();
+        if !started {
+          node.get_scripts().set_started(true);
+          node.setup();
+        }
+        started
+      });
+      if !started {
+        if node.cast(|_: &mut Collider| ()).is_some() {
+          Collider::register(node.clone(), self.root.id());
+        }
+        Scene::run_node_scripts(lua, &node, "Setup", MultiValue::new());
+      }
+    }
+  }
+
+  //? A node removed and put back (or moved elsewhere) in the same frame is left alone.
+  fn bury_nodes(&mut self, lua: &Lua) {
+    let graveyard: Vec<NodeHandle> = take_graveyard();
+    if graveyard.is_empty() {
+      return;
+    }
+    let mut seen: HashSet<u64> = self.root.descendants().iter().map(|node| node.id()).collect();
+    for dead in graveyard {
+      for node in dead.descendants() {
+        if !seen.insert(node.id()) {
+          continue;
+        }
+        if node.with(|node| node.get_scripts().is_started()) {
+          Scene::run_node_scripts(lua, &node, "Exit", MultiValue::new());
+        }
+        node.with(|node| {
+          node.get_scripts().set_started(false);
+          node.teardown();
+        });
+      }
+    }
+  }
+
+  fn run_scripts(&mut self, lua: &Lua, func_name: &str, args: MultiValue) {
+    for node in self.root.descendants() {
+      if node.with(|node| node.get_scripts().is_started()) {
+        Scene::run_node_scripts(lua, &node, func_name, args.clone());
+      }
+    }
+  }
+
+  pub fn setup(&mut self, lua: &Lua) {
+    load_persistrent(lua, &self.environment).expect("Cannot load Persistent Data");
+    if let Ok(func) = self.environment.get::<Function>("Setup") {
+      func.call::<()>(()).expect("Error during Engine Setup");
+    } else {
+      warn!("No Setup function in {}", self.path);
+    }
+
+    self.bury_nodes(lua);
+    self.start_nodes(lua);
+    Collider::refresh_broadphase(&self.root);
+    self.ready = true;
+  }
+
+  fn propagate_transforms(node: &NodeHandle, parent: ParentFrame) {
+    let frame: ParentFrame = node.with(|node| node.propagate(parent));
+    for (_, child) in node.children() {
+      Scene::propagate_transforms(&child, frame);
+    }
+  }
+
+  pub fn fixed_step(&mut self, lua: &Lua, fixed_dt: f32) {
+    for node in self.root.descendants() {
+      node.with(|node| {
+        if let Some(transform) = node.get_transform() {
+          transform.snapshot();
+        }
+      });
+    }
+
+    load_persistrent(lua, &self.environment).expect("Cannot load Persistent Data");
+    if let Ok(func) = self.environment.get::<Function>("FixedLoop") {
+      func.call::<()>(fixed_dt).expect("Error during Engine FixedLoop");
+    }
+
+    self.run_scripts(lua, "FixedLoop", MultiValue::from_vec(vec![Value::Number(fixed_dt as f64)]));
+  }
+
+  pub fn settle_transforms(&mut self) {
+    for node in self.root.descendants() {
+      node.with(|node| {
+        if let Some(transform) = node.get_transform() {
+          transform.settle();
+        }
+      });
+    }
+  }
+
+  pub fn physics_step(&mut self, dt: f32) {
+    Scene::propagate_transforms(&self.root, ParentFrame::IDENTITY);
+    physics::step(&self.root, dt);
+    Collider::refresh_broadphase(&self.root);
+  }
+
+  fn contact_info(lua: &Lua, other: &NodeHandle, normal: Vec2, point: Vec2) -> mlua::Result<Value> {
+    let info: Table = lua.create_table()?;
+    info.set("node", other.clone())?;
+    info.set("id", other.id())?;
+    info.set("kind", other.kind())?;
+    info.set("layer", other.cast(|coll: &mut Collider| coll.layer))?;
+    info.set("normal", normal)?;
+    info.set("point", point)?;
+    info.into_lua(lua)
+  }
+
+  fn fire_collision(lua: &Lua, func_name: &str, overlap: &Overlap) {
+    for (this, other, normal) in [(&overlap.a, &overlap.b, overlap.normal), (&overlap.b, &overlap.a, -overlap.normal)] {
+      let info: Value = match Scene::contact_info(lua, other, normal, overlap.point) {
+        Ok(info) => info,
+        Err(err) => {
+          eprintln!("ERROR: {}", err);
+          continue
+        }
+      };
+      for node in std::iter::once(this.clone()).chain(owning_body(this)) {
+        if node.with(|node| node.get_scripts().is_started()) {
+          Scene::run_node_scripts(lua, &node, func_name, MultiValue::from_vec(vec![info.clone()]));
+        }
+      }
+    }
+  }
+
+  //? A collider that left the tree ends its pairs too, but only the side still in the tree hears about it.
+  pub fn dispatch_collisions(&mut self, lua: &Lua) {
+    Scene::propagate_transforms(&self.root, ParentFrame::IDENTITY);
+    let mut current: HashMap<(u64, u64), Overlap> = HashMap::new();
+    for overlap in physics::overlaps(&self.root) {
+      let (id_a, id_b) = (overlap.a.id(), overlap.b.id());
+      let overlap: Overlap = if id_a < id_b { overlap } else { Overlap { a: overlap.b, b: overlap.a, normal: -overlap.normal, point: overlap.point } };
+      current.insert((id_a.min(id_b), id_a.max(id_b)), overlap);
+    }
+    for (key, overlap) in current.iter() {
+      let func_name: &str = if self.contacts.contains_key(key) { "CollisionStay" } else { "CollisionEnter" };
+      Scene::fire_collision(lua, func_name, overlap);
+    }
+    let previous: HashMap<(u64, u64), Overlap> = std::mem::replace(&mut self.contacts, current);
+    for (key, overlap) in previous.iter() {
+      if !self.contacts.contains_key(key) {
+        Scene::fire_collision(lua, "CollisionExit", overlap);
+      }
+    }
+  }
+
+  pub fn step(&mut self, lua: &Lua, dt: f32) {
+    Collider::refresh_broadphase(&self.root);
+    load_persistrent(lua, &self.environment).expect("Cannot load Persistent Data");
+    if let Ok(func) = self.environment.get::<Function>("Loop") {
+      func.call::<()>(dt).expect("Error during Engine Loop");
+    } else {
+      warn!("No Loop function in {}", self.path);
+    }
+
+    Scene::propagate_transforms(&self.root, ParentFrame::IDENTITY);
+    self.root.with(|root| root.update(dt));
+    self.run_scripts(lua, "Loop", MultiValue::from_vec(vec![Value::Number(dt as f64)]));
+
+    self.bury_nodes(lua);
+    self.start_nodes(lua);
+  }
+
+  fn drawables(world: &NodeHandle, out: &mut Vec<NodeHandle>, canvases: &mut Vec<NodeHandle>) {
+    for (_, child) in world.children() {
+      if child.cast(|_: &mut Viewport| ()).is_some() {
+        continue
+      }
+      if child.cast(|_: &mut CanvasLayer| ()).is_some() {
+        canvases.push(child);
+        continue
+      }
+      out.push(child.clone());
+      Scene::drawables(&child, out, canvases);
+    }
+  }
+
+  fn draw_world(world: &NodeHandle, include_world: bool) -> Vec<NodeHandle> {
+    let mut nodes: Vec<NodeHandle> = if include_world { vec![world.clone()] } else { Vec::new() };
+    let mut canvases: Vec<NodeHandle> = Vec::new();
+    Scene::drawables(world, &mut nodes, &mut canvases);
+    Scene::draw_sorted(nodes);
+    canvases
+  }
+
+  fn draw_canvases(canvases: Vec<NodeHandle>) {
+    let mut canvases: Vec<(i32, NodeHandle)> = canvases.into_iter()
+      .filter_map(|canvas| canvas.cast(|canvas: &mut CanvasLayer| canvas.visible.then_some(canvas.order)).flatten().map(|order| (order, canvas)))
+      .collect();
+    canvases.sort_by_key(|(order, _)| *order);
+    for (_, canvas) in canvases {
+      let nested: Vec<NodeHandle> = Scene::draw_world(&canvas, false);
+      Scene::draw_canvases(nested);
+    }
+  }
+
+  fn viewports(&self) -> Vec<NodeHandle> {
+    self.root.descendants().into_iter().filter(|node| node.cast(|_: &mut Viewport| ()).is_some()).collect()
+  }
+
+  pub fn viewport_cameras(&self) -> Vec<NodeHandle> {
+    self.viewports().into_iter().filter_map(|vp| vp.cast(|vp: &mut Viewport| vp.camera()).flatten()).collect()
+  }
+
+  fn render_viewport(viewport: &NodeHandle) {
+    let Some(pass) = viewport.cast(|vp: &mut Viewport| vp.begin_pass(viewport)).flatten() else {
+      return
+    };
+    use_render_camera(Viewport::view(&pass.camera));
+    let canvases: Vec<NodeHandle> = Scene::draw_world(&pass.world, !pass.world.same(viewport));
+    Scene::draw_canvases(canvases);
+    Viewport::end_pass();
+    refresh_camera();
+  }
+
+  fn draw_sorted(nodes: Vec<NodeHandle>) {
+    let mut draws: Vec<(DrawKey, NodeHandle)> = nodes.into_iter().filter_map(|node| {
+      let key: DrawKey = node.with(|node| {
+        let y: f32 = node.sort_y();
+        node.get_layering().map(|layering| DrawKey::new(layering, y))
+      })?;
+      Some((key, node))
+    }).collect();
+    draws.sort_by(|(a, _), (b, _)| a.compare(b));
+    for (_, node) in draws {
+      node.with(|node| node.render());
+    }
+  }
+
+  pub fn render(&mut self) {
+    Scene::propagate_transforms(&self.root, ParentFrame::IDENTITY);
+    let viewports: Vec<NodeHandle> = self.viewports();
+    let (offscreen, onscreen): (Vec<NodeHandle>, Vec<NodeHandle>) = viewports.into_iter().partition(|vp| {
+      vp.cast(|vp: &mut Viewport| vp.draws_to_texture()).unwrap_or(false)
+    });
+    for viewport in offscreen.iter() {
+      Scene::render_viewport(viewport);
+    }
+    let canvases: Vec<NodeHandle> = Scene::draw_world(&self.root, true);
+    for viewport in onscreen.iter() {
+      Scene::render_viewport(viewport);
+    }
+    Scene::draw_canvases(canvases);
+  }
+
+  pub fn teardown(&mut self, lua: &Lua) {
+    if self.ready {
+      load_persistrent(lua, &self.environment).expect("Cannot load Persistent Data");
+      if let Ok(func) = self.environment.get::<Function>("Exit")
+        && let Err(err) = func.call::<()>(()) {
+        warn!("Error during Exit in {}", self.path);
+        eprintln!("ERROR: {}", err);
+      }
+      self.run_scripts(lua, "Exit", MultiValue::new());
+    }
+
+    for node in self.root.descendants() {
+      node.with(|node| {
+        node.get_scripts().set_started(false);
+        node.get_scripts().clear();
+        node.teardown();
+      });
+    }
+    self.root.with(|root| root.get_base().clear_children());
+    take_graveyard();
+    self.contacts.clear();
+    self.ready = false;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::core::testing::Headless;
+
+  fn log(engine: &mut Headless, code: &str) -> String {
+    engine.eval::<mlua::Value>(code);
+    engine.run_frames(1, 1.0 / 60.0);
+    engine.eval::<Option<String>>("return root.probe.log").unwrap_or_default()
+  }
+
+  #[test]
+  fn collisions_enter_stay_and_exit_once_in_order() {
+    let mut engine: Headless = Headless::load("collisions.lua");
+    assert_eq!(log(&mut engine, ""), "");
+    assert_eq!(log(&mut engine, "root.block.transform.pos = Vec2(5, 0)"), "enter;");
+    assert_eq!(log(&mut engine, ""), "enter;stay;");
+    assert_eq!(log(&mut engine, "root.block.transform.pos = Vec2(100, 0)"), "enter;stay;exit;");
+    assert_eq!(log(&mut engine, ""), "enter;stay;exit;");
+  }
+
+  #[test]
+  fn freeing_a_collider_mid_contact_ends_it() {
+    let mut engine: Headless = Headless::load("collisions.lua");
+    assert_eq!(log(&mut engine, ""), "");
+    assert_eq!(log(&mut engine, "root.mine.transform.pos = Vec2(5, 5)"), "enter;");
+    assert_eq!(log(&mut engine, "root.mine = nil"), "enter;exit;");
+    assert_eq!(log(&mut engine, ""), "enter;exit;");
+  }
+}
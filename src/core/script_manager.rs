@@ -1,32 +1,21 @@
 use std::{error::Error, fs, path::PathBuf};
 
-use macroquad::{input::{KeyCode, MouseButton, is_key_down, is_key_pressed, is_key_released, is_mouse_button_down, is_mouse_button_pressed, is_mouse_button_released, mouse_position}, window::{screen_height, screen_width}};
-use mlua::{AnyUserData, Chunk, Function, Lua, MultiValue, Table, UserData, UserDataMetatable, Value};
+use mlua::{Function, Lua, MultiValue, Table, Value};
 
-use crate::core::{core::{Luable, init_env_commons, load_persistrent}, engine::Engine, keys::Stringable, vec2::Vec2};
+use crate::core::{core::{init_env_commons, load_persistrent}, proxy::NodeHandle};
 
 const MAX_STRINGIFY_DEPTH: usize = 64;
 
-pub struct ScriptManagerSecret(Table);
-
-impl UserData for ScriptManagerSecret { }
-
-impl ScriptManagerSecret {
-  pub fn from_userdata(userdata: AnyUserData) -> Result<Table, Box<dyn Error>> {
-    Ok(userdata.borrow::<ScriptManagerSecret>()?.0.clone())
-  }
-}
-
 pub struct ScriptManager {
-  scripts: Vec<(String, Chunk<'static>, Table)>,
-  environments: Option<Vec<Table>>
+  environments: Vec<Table>,
+  started: bool,
 }
 
 impl ScriptManager {
   pub fn new() -> ScriptManager { 
     ScriptManager {
-      scripts: Vec::new(),
-      environments: None
+      environments: Vec::new(),
+      started: false
     }
   }
 
@@ -44,7 +33,7 @@ impl ScriptManager {
       Value::LightUserData(_) => "<lightuserdata>".to_string(),
       Value::Other(_) => "<other>".to_string(),
       Value::Thread(_) => "<thread>".to_string(),
-      Value::UserData(_) => "<userdata>".to_string(),
+      Value::UserData(_) => ele.to_string().unwrap_or("<userdata>".to_string()),
       Value::Function(f) => {
         let info = f.info();
         let name = info.name.unwrap_or("<anonymous>".to_string());
@@ -68,142 +57,59 @@ impl ScriptManager {
     }
   }
 
-  pub fn create_environment(lua: &Lua, this: Value) -> Result<Table, Box<dyn Error>> {
+  pub fn create_environment(lua: &Lua, this: NodeHandle) -> Result<Table, Box<dyn Error>> {
     let env: Table = lua.create_table()?;
-    let thistable = this.as_table().expect("Invalid Lua Value");
-    env.set("this", Value::Table(thistable.clone()))?;
+    env.set("this", this)?;
     load_persistrent(lua, &env)?;
 
     init_env_commons(lua, &env)?;
 
-    thistable.set("add_child", lua.create_function(|_, (this, id, node): (Table, String, Table)| {
-      let later = node.clone();
-      
-      let children = match this.get::<Table>("base") {
-        Ok(base) => {
-          base.get::<Table>("children")?
-        },
-        Err(e) => match this.get::<Table>("children") {
-          Ok(tbl) => tbl,
-          Err(err) => {
-            return Err(mlua::Error::RuntimeError(format!("Seriously, dude. How did you even crash this?\n {}\n\n{}", e, err).into()))
-          }
-        }
-      };
-
-      children.set(id, node)?;
-      
-      Ok(later)
-    })?)?;
-
-    thistable.set("clear_children", lua.create_function(|_, this: Table| {
-      let children = match this.get::<Table>("base") {
-        Ok(base) => {
-          base.get::<Table>("children")?
-        },
-        Err(e) => match this.get::<Table>("children") {
-          Ok(tbl) => tbl,
-          Err(err) => {
-            return Err(mlua::Error::RuntimeError(format!("Seriously, dude. How did you even crash this?\n {}\n\n{}", e, err).into()))
-          }
-        }
-      };
-      children.clear()?;
-      Ok(())
-    })?)?;
-
-    thistable.set("remove_child", lua.create_function(|_, (this, id): (Table, String)| {
-      let children = match this.get::<Table>("base") {
-        Ok(base) => {
-          base.get::<Table>("children")?
-        },
-        Err(e) => match this.get::<Table>("children") {
-          Ok(tbl) => tbl,
-          Err(err) => {
-            return Err(mlua::Error::RuntimeError(format!("Seriously, dude. How did you even crash this?\n {}\n\n{}", e, err).into()))
-          }
-        }
-      };
-      children.set(id, Value::Nil)?;
-      Ok(())
-    })?)?;
-
     Ok(env)
   }
 
-  pub fn addScript(&mut self, path: PathBuf, lua: &Lua, this: Value) -> Result<(), Box<dyn Error>> {
+  pub fn addScript(path: PathBuf, lua: &Lua, this: NodeHandle) -> Result<(), Box<dyn Error>> {
     let tmp: PathBuf = path.clone();
     let filename: &str = tmp.file_name().ok_or_else(|| "Path has no filename")?.to_str().unwrap();
     let src: String = fs::read_to_string(path)?;
-    let fname: String = filename.to_string();
-    let chunk: Chunk<'_> = lua.load(src);
-    let env = ScriptManager::create_environment(lua, this)?;
-    self.scripts.push((fname, chunk, env));
+    let env: Table = ScriptManager::create_environment(lua, this.clone())?;
+    lua.load(src)
+      .set_environment(env.clone())
+      .set_name(filename)
+      .exec()?;
+    this.with(|node| node.get_scripts().add_environment(env));
     Ok(())
   }
 
-  pub fn loadScripts(&mut self) -> Result<(), Box<dyn Error>> {
-    let mut ret: Vec<Table> = Vec::new();
-    let tmp = std::mem::take(&mut self.scripts); //? WILL NOT BE GIVEN BACK TO THE MANAGER.
-    for (fname, chunk, env) in tmp {
-      chunk
-        .set_environment(env.clone())
-        .set_name(fname.clone())
-        .exec()?;
-      ret.push(env);
-    }
-    self.environments = Some(ret);
-    Ok(())
+  pub fn add_environment(&mut self, env: Table) {
+    self.environments.push(env);
   }
 
-  pub fn run_4all_envs(&mut self, lua: &Lua, func_name: String, args: MultiValue) -> Result<Option<Table>, Box<dyn Error>> {
-    if self.environments.is_none() {
-      return Ok(None);
-    }
-    let envs: &Vec<Table> = self.environments.as_ref().unwrap();
-    for env in envs {
-      load_persistrent(lua, env)?;
-      let func: Function = env.get(func_name.clone())?;
-      let _: Value = func.call(args.clone())?;
-    }
+  pub fn environments(&self) -> Vec<Table> {
+    self.environments.clone()
+  }
 
-    let ret = envs.last();
-    if ret.is_none() {
-      return Ok(None)
-    }
-    let ret = ret.unwrap();
-    let tbl: Table = ret.get("this")?;
-    Ok(Some(tbl))
+  //? Each environment holds 'this', so a node that keeps its scripts is never collected.
+  pub fn clear(&mut self) {
+    self.environments.clear();
   }
-}
 
-impl Luable for ScriptManager {
-  fn as_lua(&self, lua: &Lua) -> Result<Value, Box<dyn Error>> {
-    let table = lua.create_table()?;
-    if self.environments.is_none() {
-      let tmp = lua.create_userdata(ScriptManagerSecret(table))?;
-      return Ok(Value::UserData(tmp));
-    }
-    let envs = self.environments.clone().unwrap();
-    for (i, env) in envs.iter().enumerate() {
-      table.set(i, Value::Table(env.clone()))?;
-    }
-    let userdata = lua.create_userdata(ScriptManagerSecret(table))?;
-    Ok(Value::UserData(userdata))
+  pub fn is_started(&self) -> bool {
+    self.started
   }
 
-  fn from_lua(&mut self, value: Value) -> Result<(), Box<dyn Error>> {
-    
-    if let Some(userdata) = value.as_userdata() {
-      let table: Table = userdata.borrow::<ScriptManagerSecret>()?.0.clone();
-      let mut temp: Vec<Table> = Vec::new();
-      table.for_each(|_: u64, env: Table| {
-        temp.push(env);
-        Ok(())
-      })?;
-      self.environments = Some(temp);
-      return Ok(())
+  pub fn set_started(&mut self, started: bool) {
+    self.started = started;
+  }
+
+  //? Takes the environments instead of '&mut self': the node must not stay locked while its scripts touch 'this'.
+  pub fn run_4all_envs(lua: &Lua, envs: &[Table], func_name: &str, args: MultiValue) -> Result<(), Box<dyn Error>> {
+    for env in envs {
+      load_persistrent(lua, env)?;
+      let Some(func) = env.get::<Option<Function>>(func_name)? else {
+        continue;
+      };
+      let _: Value = func.call(args.clone())?;
     }
-    Err("Invalid Lua Value".into())
+    Ok(())
   }
 }
@@ -0,0 +1,424 @@
+use mlua::{FromLua, IntoLua, Lua, Table, Value};
+
+use crate::core::{broadphase::Aabb, transform::{ParentFrame, Transform}, vec2::Vec2};
+
+//? A radius or height of 0 fits the shape to the transform's size.
+#[derive(Clone)]
+pub enum Shape {
+  Rect,
+  Circle { radius: f32 },
+  Polygon { points: Vec<Vec2> },
+  Capsule { radius: f32, height: f32 },
+}
+
+impl Shape {
+  pub fn kind(&self) -> &'static str {
+    match self {
+      Shape::Rect => "rect",
+      Shape::Circle { .. } => "circle",
+      Shape::Polygon { .. } => "polygon",
+      Shape::Capsule { .. } => "capsule",
+    }
+  }
+
+  pub fn hull(&self, transform: &Transform) -> Hull {
+    let frame: ParentFrame = transform.child_frame();
+    let center: Vec2 = (Vec2::new(0.5, 0.5) - transform.origin) * transform.size;
+    let scale: f32 = frame.scale.get_x().abs().max(frame.scale.get_y().abs());
+    let place = |point: Vec2| frame.to_global(center + point);
+    let (width, height) = (transform.size.get_x().abs(), transform.size.get_y().abs());
+    match self {
+      Shape::Rect => Hull { points: transform.corners().to_vec(), radius: 0.0 },
+      Shape::Circle { radius } => {
+        let radius: f32 = if *radius > 0.0 { *radius } else { width.min(height) / 2.0 };
+        Hull { points: vec![place(Vec2::ZERO)], radius: radius * scale }
+      },
+      Shape::Polygon { points } => Hull { points: points.iter().map(|point| place(*point)).collect(), radius: 0.0 },
+      Shape::Capsule { radius, height: total } => {
+        let radius: f32 = if *radius > 0.0 { *radius } else { width / 2.0 };
+        let total: f32 = if *total > 0.0 { *total } else { height };
+        let half: f32 = (total / 2.0 - radius).max(0.0);
+        Hull { points: vec![place(Vec2::new(0.0, -half)), place(Vec2::new(0.0, half))], radius: radius * scale }
+      },
+    }
+  }
+}
+
+fn is_convex(points: &[Vec2]) -> bool {
+  let turns: Vec<f32> = (0..points.len()).map(|i| {
+    let (a, b, c) = (points[i], points[(i + 1) % points.len()], points[(i + 2) % points.len()]);
+    (b - a).dot((c - b).perp())
+  }).collect();
+  turns.iter().all(|turn| *turn >= 0.0) || turns.iter().all(|turn| *turn <= 0.0)
+}
+
+impl FromLua for Shape {
+  fn from_lua(value: Value, lua: &Lua) -> mlua::Result<Self> {
+    let (kind, table): (String, Option<Table>) = match value {
+      Value::String(kind) => (kind.to_str()?.to_string(), None),
+      Value::Table(table) => (table.get("kind")?, Some(table)),
+      _ => return Err(mlua::Error::FromLuaConversionError { from: value.type_name(), to: "Shape".to_string(), message: None })
+    };
+    let field = |name: &str| -> mlua::Result<f32> {
+      Ok(match &table {
+        Some(table) => table.get::<Option<f32>>(name)?.unwrap_or(0.0),
+        None => 0.0
+      })
+    };
+    match kind.as_str() {
+      "rect" => Ok(Shape::Rect),
+      "circle" => Ok(Shape::Circle { radius: field("radius")? }),
+      "capsule" => Ok(Shape::Capsule { radius: field("radius")?, height: field("height")? }),
+      "polygon" => {
+        let points: Vec<Vec2> = match &table {
+          Some(table) => Vec::<Vec2>::from_lua(table.get("points")?, lua)?,
+          None => Vec::new()
+        };
+        if points.len() < 3 || !is_convex(&points) {
+          return Err(mlua::Error::RuntimeError("A polygon shape needs at least 3 points forming a convex outline".to_string()))
+        }
+        Ok(Shape::Polygon { points })
+      },
+      _ => Err(mlua::Error::RuntimeError(format!("Unknown shape '{}', expected rect, circle, polygon or capsule", kind)))
+    }
+  }
+}
+
+impl IntoLua for Shape {
+  fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+    let table: Table = lua.create_table()?;
+    table.set("kind", self.kind())?;
+    match self {
+      Shape::Rect => (),
+      Shape::Circle { radius } => table.set("radius", radius)?,
+      Shape::Polygon { points } => table.set("points", points)?,
+      Shape::Capsule { radius, height } => {
+        table.set("radius", radius)?;
+        table.set("height", height)?;
+      },
+    }
+    Ok(Value::Table(table))
+  }
+}
+
+//? A circle is one point with a radius, a capsule two, and rects and polygons have no radius.
+#[derive(Clone)]
+pub struct Hull {
+  pub points: Vec<Vec2>,
+  pub radius: f32,
+}
+
+fn ray_segment(from: Vec2, direction: Vec2, a: Vec2, b: Vec2) -> Option<f32> {
+  let edge: Vec2 = b - a;
+  let denom: f32 = edge.dot(direction.perp());
+  if denom == 0.0 {
+    return None
+  }
+  let along: f32 = edge.dot((a - from).perp()) / denom;
+  let on_edge: f32 = direction.dot((a - from).perp()) / denom;
+  (along >= 0.0 && (0.0..=1.0).contains(&on_edge)).then_some(along)
+}
+
+fn ray_circle(from: Vec2, direction: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+  let offset: Vec2 = from - center;
+  let toward: f32 = offset.dot(direction);
+  let outside: f32 = offset.dot(offset) - radius * radius;
+  let disc: f32 = toward * toward - outside;
+  if (outside > 0.0 && toward > 0.0) || disc < 0.0 {
+    return None
+  }
+  Some((-toward - disc.sqrt()).max(0.0))
+}
+
+fn convex_hull(mut points: Vec<Vec2>) -> Vec<Vec2> {
+  points.sort_by(|a, b| a.get_x().total_cmp(&b.get_x()).then(a.get_y().total_cmp(&b.get_y())));
+  points.dedup();
+  if points.len() < 3 {
+    return points
+  }
+  let turns_left = |chain: &[Vec2], point: Vec2| {
+    let (a, b) = (chain[chain.len() - 2], chain[chain.len() - 1]);
+    (point - a).dot((b - a).perp()) > 0.0
+  };
+  let mut outline: Vec<Vec2> = Vec::new();
+  for pass in [points.clone(), points.into_iter().rev().collect()] {
+    let mut chain: Vec<Vec2> = Vec::new();
+    for point in pass {
+      while chain.len() >= 2 && !turns_left(&chain, point) {
+        chain.pop();
+      }
+      chain.push(point);
+    }
+    chain.pop();
+    outline.extend(chain);
+  }
+  outline
+}
+
+fn closest_on_segment(a: Vec2, b: Vec2, point: Vec2) -> Vec2 {
+  let edge: Vec2 = b - a;
+  let length: f32 = edge.dot(edge);
+  if length == 0.0 {
+    return a
+  }
+  a + edge * ((point - a).dot(edge) / length).clamp(0.0, 1.0)
+}
+
+impl Hull {
+  pub fn translated(&self, offset: Vec2) -> Hull {
+    Hull { points: self.points.iter().map(|point| *point + offset).collect(), radius: self.radius }
+  }
+
+  pub fn aabb(&self) -> Aabb {
+    let (min_x, max_x) = self.project(Vec2::new(1.0, 0.0));
+    let (min_y, max_y) = self.project(Vec2::new(0.0, 1.0));
+    Aabb { min: Vec2::new(min_x, min_y), max: Vec2::new(max_x, max_y) }
+  }
+
+  pub fn center(&self) -> Vec2 {
+    self.points.iter().fold(Vec2::ZERO, |sum, point| sum + *point) / self.points.len().max(1) as f32
+  }
+
+  pub fn project(&self, axis: Vec2) -> (f32, f32) {
+    let (min, max) = self.points.iter()
+      .map(|point| point.dot(axis))
+      .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| (min.min(value), max.max(value)));
+    (min - self.radius, max + self.radius)
+  }
+
+  fn edges(&self) -> Vec<(Vec2, Vec2)> {
+    match self.points.len() {
+      0 | 1 => Vec::new(),
+      2 => vec![(self.points[0], self.points[1])],
+      count => (0..count).map(|i| (self.points[i], self.points[(i + 1) % count])).collect()
+    }
+  }
+
+  fn edge_normals(&self) -> Vec<Vec2> {
+    self.edges().into_iter().map(|(a, b)| (b - a).perp().normalized()).filter(|normal| *normal != Vec2::ZERO).collect()
+  }
+
+  fn core_contains(&self, point: Vec2) -> bool {
+    if self.points.len() < 3 {
+      return false
+    }
+    let sides: Vec<f32> = self.edges().into_iter().map(|(a, b)| (point - a).dot((b - a).perp())).collect();
+    sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+  }
+
+  fn closest_point(&self, point: Vec2) -> Vec2 {
+    if self.points.len() == 1 {
+      return self.points[0]
+    }
+    if self.core_contains(point) {
+      return point
+    }
+    self.edges().into_iter()
+      .map(|(a, b)| closest_on_segment(a, b, point))
+      .min_by(|a, b| (*a - point).length().total_cmp(&(*b - point).length()))
+      .unwrap_or(point)
+  }
+
+  pub fn thickness(&self) -> f32 {
+    self.edge_normals().into_iter()
+      .map(|axis| {
+        let (min, max) = self.project(axis);
+        max - min
+      })
+      .reduce(f32::min)
+      .unwrap_or(self.radius * 2.0)
+  }
+
+  pub fn sat(&self, other: &Hull) -> Option<(Vec2, f32)> {
+    let mut axes: Vec<Vec2> = self.edge_normals();
+    axes.extend(other.edge_normals());
+    if self.radius > 0.0 || other.radius > 0.0 {
+      for (from, to) in [(self, other), (other, self)] {
+        for point in from.points.iter() {
+          axes.push((to.closest_point(*point) - *point).normalized());
+        }
+      }
+    }
+    axes.retain(|axis| *axis != Vec2::ZERO);
+    if axes.is_empty() {
+      axes = vec![Vec2::new(1.0, 0.0), Vec2::new(0.0, 1.0)];
+    }
+    let mut best: Option<(Vec2, f32)> = None;
+    for axis in axes {
+      let ((min_a, max_a), (min_b, max_b)) = (self.project(axis), other.project(axis));
+      let depth: f32 = max_a.min(max_b) - min_a.max(min_b);
+      if depth < 0.0 {
+        return None
+      }
+      if best.is_none_or(|(_, best)| depth < best) {
+        best = Some((axis, depth));
+      }
+    }
+    let (axis, depth) = best?;
+    let normal: Vec2 = if (other.center() - self.center()).dot(axis) < 0.0 { -axis } else { axis };
+    Some((normal, depth))
+  }
+
+  pub fn contact_point(&self, other: &Hull, normal: Vec2) -> Vec2 {
+    let tangent: Vec2 = normal.perp();
+    let side = |hull: &Hull, direction: Vec2| {
+      let reach: f32 = hull.points.iter().map(|point| point.dot(direction)).fold(f32::NEG_INFINITY, f32::max);
+      let (low, high) = hull.points.iter()
+        .filter(|point| point.dot(direction) >= reach - 0.01)
+        .map(|point| point.dot(tangent))
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), value| (low.min(value), high.max(value)));
+      ((reach + hull.radius) * direction.dot(normal), low, high)
+    };
+    let ((depth_a, low_a, high_a), (depth_b, low_b, high_b)) = (side(self, normal), side(other, -normal));
+    let along: f32 = (low_a.max(low_b) + high_a.min(high_b)) / 2.0;
+    normal * ((depth_a + depth_b) / 2.0) + tangent * along
+  }
+
+  pub fn contains(&self, point: Vec2) -> bool {
+    !self.points.is_empty() && (self.closest_point(point) - point).length() <= self.radius
+  }
+
+  pub fn nearest_point(&self, point: Vec2) -> Vec2 {
+    if self.contains(point) {
+      return point
+    }
+    let closest: Vec2 = self.closest_point(point);
+    closest + (point - closest).normalized() * self.radius
+  }
+
+  pub fn raycast(&self, from: Vec2, direction: Vec2, length: f32) -> Option<(f32, Vec2)> {
+    if self.contains(from) {
+      return Some((0.0, -direction))
+    }
+    let mut best: Option<(f32, Vec2)> = None;
+    let mut consider = |distance: Option<f32>, normal: Vec2| {
+      if let Some(distance) = distance
+        && distance <= length && best.is_none_or(|(best, _)| distance < best) {
+        best = Some((distance, normal));
+      }
+    };
+    for (a, b) in self.edges() {
+      let normal: Vec2 = (b - a).perp().normalized();
+      let normal: Vec2 = if normal.dot(direction) > 0.0 { -normal } else { normal };
+      consider(ray_segment(from, direction, a + normal * self.radius, b + normal * self.radius), normal);
+    }
+    if self.radius > 0.0 {
+      for point in self.points.iter() {
+        let distance: Option<f32> = ray_circle(from, direction, *point, self.radius);
+        consider(distance, distance.map_or(Vec2::ZERO, |distance| (from + direction * distance - *point).normalized()));
+      }
+    }
+    best
+  }
+
+  //? Hulls already touching give 0 when the motion goes into 'other', and nothing when it leaves, slides along or only grazes it.
+  pub fn sweep(&self, motion: Vec2, other: &Hull) -> Option<(f32, Vec2)> {
+    if let Some((normal, _)) = other.sat(self) {
+      return (motion.dot(normal) < 0.0).then_some((0.0, normal))
+    }
+    let length: f32 = motion.length();
+    if length == 0.0 {
+      return None
+    }
+    let difference: Vec<Vec2> = other.points.iter().flat_map(|b| self.points.iter().map(move |a| *b - *a)).collect();
+    let obstacle: Hull = Hull { points: convex_hull(difference), radius: self.radius + other.radius };
+    let (distance, normal) = obstacle.raycast(Vec2::ZERO, motion / length, length)?;
+    (motion.dot(normal) < 0.0).then_some((distance / length, normal))
+  }
+
+  //? Touching counts, like it always has for colliders.
+  pub fn overlaps(&self, other: &Hull) -> bool {
+    self.sat(other).is_some()
+  }
+
+  pub fn penetration(&self, other: &Hull) -> Option<(Vec2, f32)> {
+    self.sat(other).filter(|(_, depth)| *depth > 0.0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rect(x: f32, y: f32, width: f32, height: f32) -> Hull {
+    Hull { points: vec![Vec2::new(x, y), Vec2::new(x + width, y), Vec2::new(x + width, y + height), Vec2::new(x, y + height)], radius: 0.0 }
+  }
+
+  fn circle(x: f32, y: f32, radius: f32) -> Hull {
+    Hull { points: vec![Vec2::new(x, y)], radius }
+  }
+
+  fn assert_near(actual: Vec2, expected: Vec2) {
+    assert!((actual - expected).length() < 1e-4, "{:?} is not {:?}", actual, expected);
+  }
+
+  #[test]
+  fn circle_against_rect() {
+    let wall: Hull = rect(0.0, 0.0, 10.0, 10.0);
+    let (normal, depth) = wall.penetration(&circle(13.0, 5.0, 4.0)).expect("Circle should overlap the side");
+    assert_near(normal, Vec2::new(1.0, 0.0));
+    assert!((depth - 1.0).abs() < 1e-4);
+    assert!(!wall.overlaps(&circle(15.0, 5.0, 4.0)));
+    assert!(!wall.overlaps(&circle(12.0, 12.0, 2.0)));
+    assert!(wall.overlaps(&circle(11.0, 11.0, 2.0)));
+  }
+
+  #[test]
+  fn capsule_against_polygon() {
+    let capsule: Hull = Hull { points: vec![Vec2::new(0.0, -5.0), Vec2::new(0.0, 5.0)], radius: 2.0 };
+    let triangle = |x: f32| Hull { points: vec![Vec2::new(x, 0.0), Vec2::new(x + 7.0, -5.0), Vec2::new(x + 7.0, 5.0)], radius: 0.0 };
+    assert!(!capsule.overlaps(&triangle(3.0)));
+    let (normal, depth) = capsule.penetration(&triangle(1.5)).expect("Triangle tip should reach into the capsule");
+    assert_near(normal, Vec2::new(1.0, 0.0));
+    assert!((depth - 0.5).abs() < 1e-4);
+    let below: Hull = Hull { points: vec![Vec2::new(2.0, 7.0), Vec2::new(9.0, 2.0), Vec2::new(9.0, 12.0)], radius: 0.0 };
+    assert!(!capsule.overlaps(&below));
+  }
+
+  #[test]
+  fn touching_is_not_penetrating() {
+    let left: Hull = rect(0.0, 0.0, 10.0, 10.0);
+    let touching: Hull = rect(10.0, 0.0, 10.0, 10.0);
+    assert!(left.overlaps(&touching));
+    assert!(left.penetration(&touching).is_none());
+    let (normal, depth) = left.penetration(&rect(9.0, 0.0, 10.0, 10.0)).expect("Rects should overlap");
+    assert_near(normal, Vec2::new(1.0, 0.0));
+    assert!((depth - 1.0).abs() < 1e-4);
+  }
+
+  #[test]
+  fn raycast_distance_and_normal() {
+    let (distance, normal) = rect(0.0, 0.0, 10.0, 10.0).raycast(Vec2::new(-5.0, 5.0), Vec2::new(1.0, 0.0), 20.0).expect("Ray should hit the side");
+    assert!((distance - 5.0).abs() < 1e-4);
+    assert_near(normal, Vec2::new(-1.0, 0.0));
+    let (distance, normal) = circle(10.0, 0.0, 2.0).raycast(Vec2::ZERO, Vec2::new(1.0, 0.0), 20.0).expect("Ray should hit the circle");
+    assert!((distance - 8.0).abs() < 1e-4);
+    assert_near(normal, Vec2::new(-1.0, 0.0));
+    assert!(circle(10.0, 0.0, 2.0).raycast(Vec2::ZERO, Vec2::new(1.0, 0.0), 7.0).is_none());
+  }
+
+  #[test]
+  fn sweep_time_of_impact_and_normal() {
+    let wall: Hull = rect(5.0, -5.0, 2.0, 10.0);
+    let (time, normal) = circle(0.0, 0.0, 1.0).sweep(Vec2::new(10.0, 0.0), &wall).expect("Circle should hit the wall");
+    assert!((time - 0.4).abs() < 1e-4);
+    assert_near(normal, Vec2::new(-1.0, 0.0));
+    let (time, normal) = rect(0.0, -20.0, 2.0, 2.0).sweep(Vec2::new(0.0, 30.0), &wall.translated(Vec2::new(-5.0, 0.0))).expect("Box should land on the wall");
+    assert!((time - 13.0 / 30.0).abs() < 1e-4);
+    assert_near(normal, Vec2::new(0.0, -1.0));
+    assert!(circle(0.0, 0.0, 1.0).sweep(Vec2::new(-10.0, 0.0), &wall).is_none());
+    assert!(circle(0.0, 0.0, 1.0).sweep(Vec2::new(3.0, 0.0), &wall).is_none());
+  }
+
+  #[test]
+  fn sweep_from_contact() {
+    let wall: Hull = rect(5.0, -5.0, 2.0, 10.0);
+    let resting: Hull = circle(4.0, 0.0, 1.0);
+    let (time, normal) = resting.sweep(Vec2::new(1.0, 0.0), &wall).expect("Pushing into the wall should stop at once");
+    assert_eq!(time, 0.0);
+    assert_near(normal, Vec2::new(-1.0, 0.0));
+    assert!(resting.sweep(Vec2::new(-1.0, 0.0), &wall).is_none());
+    assert!(resting.sweep(Vec2::new(0.0, 1.0), &wall).is_none());
+    assert!(circle(0.0, -6.0, 1.0).sweep(Vec2::new(10.0, 0.0), &wall).is_none());
+  }
+}
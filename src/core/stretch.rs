@@ -0,0 +1,150 @@
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+use macroquad::{camera::{Camera2D, set_camera, set_default_camera}, input::mouse_position, math::Rect, texture::{DrawTextureParams, FilterMode, RenderTarget, draw_texture_ex, render_target}, window::{clear_background, screen_height, screen_width}};
+use mlua::Table;
+
+use crate::core::{color::Color, engine::headless_window, vec2::Vec2};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum StretchMode {
+  Integer,
+  Keep,
+  Expand,
+}
+
+impl StretchMode {
+  fn parse(name: &str) -> mlua::Result<StretchMode> {
+    match name {
+      "integer" => Ok(StretchMode::Integer),
+      "keep" => Ok(StretchMode::Keep),
+      "expand" => Ok(StretchMode::Expand),
+      _ => Err(mlua::Error::RuntimeError(format!("Unknown StretchMode '{}', expected integer, keep or expand", name)))
+    }
+  }
+}
+
+struct Stretch {
+  size: Vec2,
+  mode: StretchMode,
+  target: Option<RenderTarget>,
+}
+
+lazy_static! {
+  static ref STRETCH: Arc<RwLock<Option<Stretch>>> = Arc::new(RwLock::new(None));
+}
+
+pub fn load_stretch(env: &Table) -> mlua::Result<()> {
+  let stretch: Option<Stretch> = match env.get::<Option<Vec2>>("VirtualSize")? {
+    Some(size) if size.get_x() > 0.0 && size.get_y() > 0.0 => {
+      let mode: StretchMode = StretchMode::parse(&env.get::<Option<String>>("StretchMode")?.unwrap_or("keep".to_string()))?;
+      Some(Stretch { size, mode, target: None })
+    },
+    _ => None
+  };
+  *STRETCH.write().unwrap() = stretch;
+  Ok(())
+}
+
+#[derive(Clone, Copy)]
+pub struct Layout {
+  pub size: Vec2,
+  pub scale: f32,
+  pub offset: Vec2,
+}
+
+impl Layout {
+  pub fn to_virtual(self, point: Vec2) -> Vec2 {
+    (point - self.offset) / self.scale
+  }
+}
+
+fn window_size() -> Vec2 {
+  headless_window().unwrap_or_else(|| Vec2::new(screen_width(), screen_height()))
+}
+
+pub fn layout() -> Layout {
+  let window: Vec2 = window_size();
+  let Some((size, mode)) = STRETCH.read().unwrap().as_ref().map(|stretch| (stretch.size, stretch.mode)) else {
+    return Layout { size: window, scale: 1.0, offset: Vec2::ZERO }
+  };
+  let fit: f32 = (window.get_x() / size.get_x()).min(window.get_y() / size.get_y());
+  let (size, scale): (Vec2, f32) = match mode {
+    StretchMode::Integer => (size, fit.floor().max(1.0)),
+    StretchMode::Keep => (size, fit),
+    StretchMode::Expand => ((window / fit).floor().into(), fit),
+  };
+  Layout { size, scale, offset: ((window - size * scale) / 2.0).floor().into() }
+}
+
+pub fn virtual_size() -> Vec2 {
+  layout().size
+}
+
+pub fn virtual_mouse() -> Vec2 {
+  let (x, y) = mouse_position();
+  layout().to_virtual(Vec2::new(x, y))
+}
+
+pub fn virtual_target() -> Option<RenderTarget> {
+  STRETCH.read().unwrap().as_ref().and_then(|stretch| stretch.target.clone())
+}
+
+//? GL rows of such a target count from the top, like screen pixels.
+pub fn target_camera(size: Vec2, target: RenderTarget) -> Camera2D {
+  let mut camera: Camera2D = Camera2D::from_display_rect(Rect::new(0.0, 0.0, size.get_x(), size.get_y()));
+  camera.zoom.y = -camera.zoom.y;
+  camera.render_target = Some(target);
+  camera
+}
+
+pub fn screen_camera(size: Vec2) -> Camera2D {
+  match virtual_target() {
+    Some(target) => target_camera(size, target),
+    None => Camera2D::from_display_rect(Rect::new(0.0, 0.0, size.get_x(), size.get_y()))
+  }
+}
+
+pub fn screen_viewport(pos: Vec2, size: Vec2) -> (i32, i32, i32, i32) {
+  let y: f32 = if virtual_target().is_some() { pos.get_y() } else { screen_height() - pos.get_y() - size.get_y() };
+  (pos.get_x() as i32, y as i32, size.get_x() as i32, size.get_y() as i32)
+}
+
+pub fn use_base_camera() {
+  match virtual_target() {
+    Some(target) => set_camera(&target_camera(layout().size, target)),
+    None => set_default_camera()
+  }
+}
+
+pub fn begin_frame(background: Color) {
+  let size: Vec2 = layout().size;
+  if let Some(stretch) = STRETCH.write().unwrap().as_mut() {
+    let (width, height) = (size.get_x().max(1.0) as u32, size.get_y().max(1.0) as u32);
+    let outdated: bool = match &stretch.target {
+      Some(target) => target.texture.width() as u32 != width || target.texture.height() as u32 != height,
+      None => true
+    };
+    if outdated {
+      let target: RenderTarget = render_target(width, height);
+      target.texture.set_filter(FilterMode::Nearest);
+      stretch.target = Some(target);
+    }
+  }
+  use_base_camera();
+  clear_background(background.into());
+}
+
+pub fn end_frame() {
+  let Some(target) = virtual_target() else {
+    return
+  };
+  let layout: Layout = layout();
+  set_default_camera();
+  clear_background(Color::new(0xff000000).into());
+  let size: Vec2 = layout.size * layout.scale;
+  draw_texture_ex(&target.texture, layout.offset.get_x(), layout.offset.get_y(), Color::new(0xffffffff).into(), DrawTextureParams {
+    dest_size: Some(size.into()),
+    ..Default::default()
+  });
+}
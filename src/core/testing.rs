@@ -0,0 +1,48 @@
+use std::{ops::{Deref, DerefMut}, sync::{Mutex, MutexGuard}};
+
+use mlua::{FromLua, Table};
+
+use crate::core::engine::Engine;
+
+//? Engines share process-wide state (scene requests, the main camera, the collider manager), so tests using one run one at a time.
+static ENGINE_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn fixture(name: &str) -> String {
+  format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+pub fn serial() -> MutexGuard<'static, ()> {
+  ENGINE_LOCK.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+pub struct Headless {
+  engine: Engine,
+  _serial: MutexGuard<'static, ()>,
+}
+
+impl Headless {
+  pub fn load(name: &str) -> Headless {
+    let serial: MutexGuard<'static, ()> = serial();
+    let engine: Engine = Engine::load_headless(&fixture(name)).unwrap_or_else(|err| panic!("Cannot load {}: {}", name, err));
+    Headless { engine, _serial: serial }
+  }
+
+  //? Evaluates 'code' in the current scene's environment, where its globals and 'root' live.
+  pub fn eval<T: FromLua>(&self, code: &str) -> T {
+    let env: Table = self.engine.environment();
+    self.engine.lua().load(code).set_environment(env).eval().unwrap_or_else(|err| panic!("Cannot evaluate '{}': {}", code, err))
+  }
+}
+
+impl Deref for Headless {
+  type Target = Engine;
+  fn deref(&self) -> &Engine {
+    &self.engine
+  }
+}
+
+impl DerefMut for Headless {
+  fn deref_mut(&mut self) -> &mut Engine {
+    &mut self.engine
+  }
+}
@@ -1,68 +1,210 @@
-use std::error::Error;
+use mlua::{FromLua, IntoLua, Lua, MetaMethod, UserData, UserDataMethods, Value};
 
-use macroquad::math::Rect;
-use mlua::{Lua, Table, Value};
+use crate::core::{engine::{main_camera, render_alpha}, proxy::Place, vec2::Vec2};
 
-use crate::core::{core::Luable, engine::main_camera, vec2::Vec2};
+#[derive(Clone, Copy)]
+pub struct ParentFrame {
+  pub pos: Vec2,
+  pub interpolated: Vec2,
+  pub rotation: f32,
+  pub scale: Vec2,
+  //? Set below a canvas layer: positions are screen pixels and the camera is left out.
+  pub screen: bool,
+}
+
+impl ParentFrame {
+  pub const IDENTITY: ParentFrame = ParentFrame { pos: Vec2::ZERO, interpolated: Vec2::ZERO, rotation: 0.0, scale: Vec2::ONE, screen: false };
+  pub const SCREEN: ParentFrame = ParentFrame { screen: true, ..ParentFrame::IDENTITY };
+
+  pub fn offset(&self, pos: Vec2, interpolated: Vec2) -> ParentFrame {
+    ParentFrame { pos: self.to_global(pos), interpolated: self.to_global_interpolated(interpolated), rotation: self.rotation, scale: self.scale, screen: self.screen }
+  }
+
+  pub fn to_global(self, point: Vec2) -> Vec2 {
+    self.pos + (point * self.scale).rotated(self.rotation)
+  }
+
+  pub fn to_global_interpolated(self, point: Vec2) -> Vec2 {
+    self.interpolated + (point * self.scale).rotated(self.rotation)
+  }
+
+  pub fn to_local(self, point: Vec2) -> Vec2 {
+    (point - self.pos).rotated(-self.rotation) / self.scale
+  }
+}
+
+#[derive(Clone, Copy)]
+pub struct ScreenRect {
+  pub pos: Vec2,
+  pub size: Vec2,
+  pub origin: Vec2,
+  pub rotation: f32,
+}
 
 #[derive(Clone)]
 pub struct Transform {
   pub pos: Vec2,
   pub size: Vec2,
-  pub scale: f32,
+  pub scale: Vec2,
+  pub rotation: f32,
+  pub origin: Vec2,
+  pub prev_pos: Option<Vec2>,
+  //? Where the last fixed step left 'pos'; once a script moves the node past it there is nothing to interpolate.
+  pub fixed_pos: Option<Vec2>,
+  //? 'pos', 'rotation' and 'scale' are relative to this, which the scene refreshes from the parent every frame.
+  pub parent: ParentFrame,
 }
 
 impl Transform {
   pub fn new(pos: Vec2, size: Vec2) -> Transform {
-    Transform { pos, size, scale: 1.0 }
+    Transform { pos, size, scale: Vec2::ONE, rotation: 0.0, origin: Vec2::ZERO, prev_pos: None, fixed_pos: None, parent: ParentFrame::IDENTITY }
+  }
+
+  pub fn replace(&mut self, other: Transform) {
+    let parent: ParentFrame = self.parent;
+    *self = other;
+    self.parent = parent;
+  }
+
+  pub fn global_pos(&self) -> Vec2 {
+    self.parent.to_global(self.pos)
+  }
+
+  pub fn global_scale(&self) -> Vec2 {
+    self.parent.scale * self.scale
+  }
+
+  pub fn global_rotation(&self) -> f32 {
+    self.parent.rotation + self.rotation
   }
 
-  pub fn instersects(&self, transform: &Transform) -> bool {
-    let actual_size = self.size * self.scale;
-    let a = Rect::new(self.pos.get_x() as f32, self.pos.get_y() as f32, actual_size.get_x() as f32, actual_size.get_y() as f32);
-    let actual_size = transform.size * transform.scale;
-    let b = Rect::new(transform.pos.get_x() as f32, transform.pos.get_y() as f32, actual_size.get_x() as f32, actual_size.get_y() as f32);
-    a.overlaps(&b)
+  pub fn child_frame(&self) -> ParentFrame {
+    ParentFrame { pos: self.global_pos(), interpolated: self.interpolated_global_pos(), rotation: self.global_rotation(), scale: self.global_scale(), screen: self.parent.screen }
+  }
+
+  pub fn interpolated_global_pos(&self) -> Vec2 {
+    self.parent.to_global_interpolated(self.interpolated_pos())
+  }
+
+  pub fn snapshot(&mut self) {
+    self.prev_pos = Some(self.pos);
+  }
+
+  pub fn settle(&mut self) {
+    self.fixed_pos = Some(self.pos);
+  }
+
+  pub fn interpolated_pos(&self) -> Vec2 {
+    match self.prev_pos {
+      Some(prev) if self.fixed_pos == Some(self.pos) => prev + (self.pos - prev) * render_alpha(),
+      _ => self.pos
+    }
+  }
+
+  pub fn corners(&self) -> [Vec2; 4] {
+    let frame: ParentFrame = self.child_frame();
+    let top_left: Vec2 = -(self.origin * self.size);
+    let (width, height) = (Vec2::new(self.size.get_x(), 0.0), Vec2::new(0.0, self.size.get_y()));
+    [top_left, top_left + width, top_left + self.size, top_left + height].map(|corner| frame.to_global(corner))
   }
 
   pub fn contains(&self, pos: Vec2) -> bool {
-    let actual_size = self.size * self.scale;
-    let a = Rect::new(self.pos.get_x() as f32, self.pos.get_y() as f32, actual_size.get_x() as f32, actual_size.get_y() as f32);
-    let tmp = macroquad::math::Vec2::new(pos.get_x() as f32, pos.get_y() as f32);
-    a.contains(tmp)
+    let local: Vec2 = self.child_frame().to_local(pos) + self.origin * self.size;
+    let inside = |value: f32, extent: f32| value >= extent.min(0.0) && value <= extent.max(0.0);
+    inside(local.get_x(), self.size.get_x()) && inside(local.get_y(), self.size.get_y())
+  }
+
+  pub fn get_camera_relative(&self) -> ScreenRect {
+    let (pos, size, rotation): (Vec2, Vec2, f32) = (self.interpolated_global_pos(), self.size * self.global_scale(), self.global_rotation());
+    match main_camera().filter(|_| !self.parent.screen) {
+      Some(cam) => ScreenRect { pos: cam.to_screen(pos), size: size * cam.zoom, origin: self.origin, rotation: rotation - cam.rotation },
+      None => ScreenRect { pos, size, origin: self.origin, rotation }
+    }
   }
+}
 
-  pub fn get_camera_relative(&self) -> (Vec2, Vec2) {
-    let (actual_position, actual_size): (Vec2, Vec2) = if let Some(cam) = main_camera().as_ref() {
-      (self.pos - cam.transform.pos, self.size * self.scale / cam.focal_length)
-    } else {
-      (self.pos, self.size * self.scale)
-    };
-    (actual_position, actual_size)
+fn scale_from_lua(value: Value, lua: &Lua) -> mlua::Result<Vec2> {
+  match value {
+    Value::Integer(_) | Value::Number(_) => {
+      let scale: f32 = f32::from_lua(value, lua)?;
+      Ok(Vec2::new(scale, scale))
+    },
+    other => Vec2::from_lua(other, lua)
   }
 }
 
-impl Luable for Transform {
-  fn as_lua(&self, lua: &Lua) -> Result<Value, Box<dyn Error>> {
-    let pos: Value = self.pos.as_lua(lua)?;    
-    let size: Value = self.size.as_lua(lua)?;
-    let table: Table = lua.create_table()?;
-    table.set("pos", pos)?;
-    table.set("size", size)?;
-    table.set("scale", Value::Number(self.scale as f64))?;
-    Ok(Value::Table(table))
-  }
-
-  fn from_lua(&mut self, value: Value) -> Result<(), Box<dyn Error>> {
-    if let Some(tbl) = value.as_table() {
-      let pos: Value = tbl.get("pos")?;
-      self.pos.from_lua(pos)?;
-      let size: Value = tbl.get("size")?;
-      self.size.from_lua(size)?;
-      let scale: f32 = tbl.get("scale")?;
-      self.scale = scale;
-      return Ok(())
+impl FromLua for Transform {
+  fn from_lua(value: Value, lua: &Lua) -> mlua::Result<Self> {
+    match &value {
+      Value::UserData(ud) if ud.is::<Place<Transform>>() => Ok(ud.borrow::<Place<Transform>>()?.get()),
+      Value::Table(tbl) => {
+        let mut transform: Transform = Transform::new(tbl.get("pos")?, tbl.get("size")?);
+        if let Some(scale) = tbl.get::<Option<Value>>("scale")? {
+          transform.scale = scale_from_lua(scale, lua)?;
+        }
+        transform.rotation = tbl.get::<Option<f32>>("rotation")?.unwrap_or(0.0);
+        transform.origin = tbl.get::<Option<Vec2>>("origin")?.unwrap_or(Vec2::ZERO);
+        Ok(transform)
+      },
+      _ => Err(mlua::Error::FromLuaConversionError { from: value.type_name(), to: "Transform".to_string(), message: None })
     }
-    Err("Invalid Lua Value".into())
   }
-}
\ No newline at end of file
+}
+
+impl IntoLua for Transform {
+  fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+    Place::owned(self).into_lua(lua)
+  }
+}
+
+impl UserData for Place<Transform> {
+  fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+    methods.add_meta_method(MetaMethod::Index, |lua, this, key: String| {
+      match key.as_str() {
+        "pos" => this.map(|transform| &mut transform.pos).into_lua(lua),
+        "size" => this.map(|transform| &mut transform.size).into_lua(lua),
+        "scale" => this.map(|transform| &mut transform.scale).into_lua(lua),
+        "rotation" => this.get().rotation.into_lua(lua),
+        "origin" => this.map(|transform| &mut transform.origin).into_lua(lua),
+        "prev_pos" => this.get().prev_pos.into_lua(lua),
+        _ => Ok(Value::Nil)
+      }
+    });
+
+    methods.add_meta_method(MetaMethod::NewIndex, |lua, this, (key, value): (String, Value)| {
+      match key.as_str() {
+        "pos" => {
+          let pos: Vec2 = Vec2::from_lua(value, lua)?;
+          this.with(|transform| transform.pos = pos);
+        },
+        "size" => {
+          let size: Vec2 = Vec2::from_lua(value, lua)?;
+          this.with(|transform| transform.size = size);
+        },
+        "scale" => {
+          let scale: Vec2 = scale_from_lua(value, lua)?;
+          this.with(|transform| transform.scale = scale);
+        },
+        "rotation" => {
+          let rotation: f32 = f32::from_lua(value, lua)?;
+          this.with(|transform| transform.rotation = rotation);
+        },
+        "origin" => {
+          let origin: Vec2 = Vec2::from_lua(value, lua)?;
+          this.with(|transform| transform.origin = origin);
+        },
+        _ => return Err(mlua::Error::RuntimeError(format!("Transform has no field '{}'", key)))
+      }
+      Ok(())
+    });
+
+    methods.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+      let transform: Transform = this.get();
+      Ok(format!(
+        "Transform(pos: Vec2({}, {}), size: Vec2({}, {}), scale: Vec2({}, {}), rotation: {}, origin: Vec2({}, {}))",
+        transform.pos.get_x(), transform.pos.get_y(), transform.size.get_x(), transform.size.get_y(),
+        transform.scale.get_x(), transform.scale.get_y(), transform.rotation, transform.origin.get_x(), transform.origin.get_y()
+      ))
+    });
+  }
+}
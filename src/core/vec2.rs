@@ -1,46 +1,63 @@
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
-use mlua::{Table, Value};
+use mlua::{FromLua, IntoLua, Lua, MetaMethod, UserData, UserDataMethods, Value};
 
-use crate::core::core::Luable;
+use crate::core::{ivec2::IVec2, proxy::{Place, method}};
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Vec2 {
-  x: i32,
-  y: i32
+  x: f32,
+  y: f32
 }
 
 impl Vec2 {
-  pub const ZERO: Vec2 = Self {x: 0, y: 0};
-  pub const ONE: Vec2 = Self {x: 1, y: 1};
+  pub const ZERO: Vec2 = Self {x: 0.0, y: 0.0};
+  pub const ONE: Vec2 = Self {x: 1.0, y: 1.0};
 
-  pub fn new(x: i32, y: i32) -> Self {
+  pub fn new(x: f32, y: f32) -> Self {
     Self {x: x, y: y}
   }
 
-  pub fn dot(self, other: Self) -> i32 {
+  pub fn dot(self, other: Self) -> f32 {
     self.x * other.x + self.y * other.y
   }
 
-  pub fn as_slice(&self) -> [i32; 2] {
+  pub fn as_slice(&self) -> [f32; 2] {
     [self.x, self.y]
   }
 
-  pub fn get_x(&self) -> i32 {
+  pub fn get_x(&self) -> f32 {
     self.x
   }
 
-  pub fn get_y(&self) -> i32 {
+  pub fn get_y(&self) -> f32 {
     self.y
   }
 
+  pub fn length(&self) -> f32 {
+    self.dot(*self).sqrt()
+  }
+
+  pub fn normalized(&self) -> Vec2 {
+    let length: f32 = self.length();
+    if length == 0.0 { Vec2::ZERO } else { *self / length }
+  }
 
-  pub fn get_fx(&self) -> f32 {
-    self.x as f32
+  pub fn perp(&self) -> Vec2 {
+    Vec2::new(-self.y, self.x)
+  }
+
+  //? Positive angles turn clockwise on screen, since Y points down.
+  pub fn rotated(&self, radians: f32) -> Vec2 {
+    if radians == 0.0 {
+      return *self
+    }
+    let (sin, cos) = radians.sin_cos();
+    Vec2::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
   }
 
-  pub fn get_fy(&self) -> f32 {
-    self.y as f32
+  pub fn floor(&self) -> IVec2 {
+    IVec2::new(self.x.floor() as i32, self.y.floor() as i32)
   }
 }
 
@@ -62,16 +79,10 @@ impl Mul for Vec2 {
     Vec2::new(self.x * rhs.x, self.y * rhs.y)
   }
 }
-impl Mul<i32> for Vec2 {
-  type Output = Self;
-  fn mul(self, rhs: i32) -> Self::Output {
-    Vec2::new(self.x * rhs, self.y * rhs)
-  }
-}
 impl Mul<f32> for Vec2 {
   type Output = Self;
   fn mul(self, rhs: f32) -> Self::Output {
-    Vec2::new((self.x as f32 * rhs) as i32, (self.y as f32 * rhs) as i32)
+    Vec2::new(self.x * rhs, self.y * rhs)
   }
 }
 impl Div for Vec2 {
@@ -80,16 +91,10 @@ impl Div for Vec2 {
     Vec2::new(self.x / rhs.x, self.y / rhs.y)
   }
 }
-impl Div<i32> for Vec2 {
-  type Output = Self;
-  fn div(self, rhs: i32) -> Self::Output {
-    Vec2::new(self.x / rhs, self.y / rhs)
-  }
-}
 impl Div<f32> for Vec2 {
   type Output = Self;
   fn div(self, rhs: f32) -> Self::Output {
-    Vec2::new((self.x as f32 / rhs) as i32, (self.y as f32 / rhs) as i32)
+    Vec2::new(self.x / rhs, self.y / rhs)
   }
 }
 impl Neg for Vec2 {
@@ -118,16 +123,10 @@ impl Mul<&Vec2> for &Vec2 {
     Vec2::new(self.x * rhs.x, self.y * rhs.y)
   }
 }
-impl Mul<i32> for &Vec2 {
-  type Output = Vec2;
-  fn mul(self, rhs: i32) -> Self::Output {
-    Vec2::new(self.x * rhs, self.y * rhs)
-  }
-}
 impl Mul<f32> for &Vec2 {
   type Output = Vec2;
   fn mul(self, rhs: f32) -> Self::Output {
-    Vec2::new((self.x as f32 * rhs) as i32, (self.y as f32 * rhs) as i32)
+    Vec2::new(self.x * rhs, self.y * rhs)
   }
 }
 impl Div<&Vec2> for &Vec2 {
@@ -136,16 +135,10 @@ impl Div<&Vec2> for &Vec2 {
     Vec2::new(self.x / rhs.x, self.y / rhs.y)
   }
 }
-impl Div<i32> for &Vec2 {
-  type Output = Vec2;
-  fn div(self, rhs: i32) -> Self::Output {
-    Vec2::new(self.x / rhs, self.y / rhs)
-  }
-}
 impl Div<f32> for &Vec2 {
   type Output = Vec2;
   fn div(self, rhs: f32) -> Self::Output {
-    Vec2::new((self.x as f32 / rhs) as i32, (self.y as f32 / rhs) as i32)
+    Vec2::new(self.x / rhs, self.y / rhs)
   }
 }
 
@@ -161,7 +154,12 @@ impl PartialEq for Vec2 {
     self.x == other.x && self.y == other.y
   }
 }
-impl Eq for Vec2 { }
+
+impl From<IVec2> for Vec2 {
+  fn from(value: IVec2) -> Self {
+    Vec2::new(value.get_x() as f32, value.get_y() as f32)
+  }
+}
 
 impl AddAssign for Vec2 {
   fn add_assign(&mut self, rhs: Self) {
@@ -183,16 +181,6 @@ impl DivAssign for Vec2 {
     *self = *self / rhs;   
   }
 }
-impl MulAssign<i32> for Vec2 {
-  fn mul_assign(&mut self, rhs: i32) {
-    *self = *self * rhs;
-  }
-}
-impl DivAssign<i32> for Vec2 {
-  fn div_assign(&mut self, rhs: i32) {
-    *self = *self / rhs;   
-  }
-}
 impl MulAssign<f32> for Vec2 {
   fn mul_assign(&mut self, rhs: f32) {
     *self = *self * rhs;
@@ -206,30 +194,105 @@ impl DivAssign<f32> for Vec2 {
 
 impl Into<macroquad::math::Vec2> for Vec2 {
   fn into(self) -> macroquad::math::Vec2 {
-    macroquad::math::Vec2 { x: self.x as f32, y: self.y as f32 }
+    macroquad::math::Vec2 { x: self.x, y: self.y }
   }
 }
 
-impl Luable for Vec2 {
-  fn as_lua(&self, lua: &mlua::Lua) -> Result<mlua::Value, Box<dyn std::error::Error>> {
-    let table: Table = lua.create_table()?;
-    table.set("x", Value::Integer(self.get_x() as i64))?;
-    table.set("y", Value::Integer(self.get_y() as i64))?;
-    table.set("dot", lua.create_function(|_, (this, other): (Table, Table)| {
-      let ret: i64 = this.get::<i64>("x")? * other.get::<i64>("x")? + this.get::<i64>("y")? * other.get::<i64>("y")?;
-      Ok(Value::Integer(ret))
-    })?)?;
-    Ok(Value::Table(table))
+impl FromLua for Vec2 {
+  fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+    match &value {
+      Value::UserData(ud) if ud.is::<Place<Vec2>>() => Ok(ud.borrow::<Place<Vec2>>()?.get()),
+      Value::UserData(ud) if ud.is::<Place<IVec2>>() => Ok(ud.borrow::<Place<IVec2>>()?.get().into()),
+      Value::Table(table) => {
+        let x: f32 = table.get("x")?;
+        let y: f32 = table.get("y")?;
+        Ok(Vec2::new(x, y))
+      },
+      _ => Err(mlua::Error::FromLuaConversionError { from: value.type_name(), to: "Vec2".to_string(), message: None })
+    }
   }
+}
+
+impl IntoLua for Vec2 {
+  fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+    Place::owned(self).into_lua(lua)
+  }
+}
+
+enum Operand {
+  Vector(Vec2),
+  Scalar(f32),
+}
 
-  fn from_lua(&mut self, value: Value) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(table) = value.as_table() {
-      let x: i64 = table.get("x")?;
-      let y: i64 = table.get("y")?;
-      self.x = x as i32;
-      self.y = y as i32;
-      return Ok(())
+impl Operand {
+  fn from_lua(value: Value, lua: &Lua) -> mlua::Result<Operand> {
+    match value {
+      Value::Integer(i) => Ok(Operand::Scalar(i as f32)),
+      Value::Number(n) => Ok(Operand::Scalar(n as f32)),
+      other => Ok(Operand::Vector(Vec2::from_lua(other, lua)?)),
     }
-    Err("Invalid Lua Value".into())
+  }
+}
+
+impl UserData for Place<Vec2> {
+  fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+    methods.add_meta_method(MetaMethod::Index, |lua, this, key: String| {
+      match key.as_str() {
+        "x" => this.get().x.into_lua(lua),
+        "y" => this.get().y.into_lua(lua),
+        "dot" => method(lua, "Vec2.dot", |_, (this, other): (Vec2, Vec2)| {
+          Ok(this.dot(other))
+        }),
+        "floor" => method(lua, "Vec2.floor", |_, this: Vec2| {
+          Ok(this.floor())
+        }),
+        "length" => method(lua, "Vec2.length", |_, this: Vec2| {
+          Ok(this.length())
+        }),
+        "normalized" => method(lua, "Vec2.normalized", |_, this: Vec2| {
+          Ok(this.normalized())
+        }),
+        _ => Ok(Value::Nil)
+      }
+    });
+
+    methods.add_meta_method(MetaMethod::NewIndex, |_, this, (key, value): (String, f32)| {
+      match key.as_str() {
+        "x" => this.with(|vec| vec.x = value),
+        "y" => this.with(|vec| vec.y = value),
+        _ => return Err(mlua::Error::RuntimeError(format!("Vec2 has no field '{}'", key)))
+      }
+      Ok(())
+    });
+
+    methods.add_meta_function(MetaMethod::Add, |lua, (a, b): (Vec2, Vec2)| {
+      (a + b).into_lua(lua)
+    });
+    methods.add_meta_function(MetaMethod::Sub, |lua, (a, b): (Vec2, Vec2)| {
+      (a - b).into_lua(lua)
+    });
+    methods.add_meta_function(MetaMethod::Mul, |lua, (a, b): (Value, Value)| {
+      match (Operand::from_lua(a, lua)?, Operand::from_lua(b, lua)?) {
+        (Operand::Vector(a), Operand::Vector(b)) => (a * b).into_lua(lua),
+        (Operand::Vector(v), Operand::Scalar(s)) | (Operand::Scalar(s), Operand::Vector(v)) => (v * s).into_lua(lua),
+        (Operand::Scalar(a), Operand::Scalar(b)) => (a * b).into_lua(lua),
+      }
+    });
+    methods.add_meta_function(MetaMethod::Div, |lua, (a, b): (Vec2, Value)| {
+      match Operand::from_lua(b, lua)? {
+        Operand::Vector(b) => (a / b).into_lua(lua),
+        Operand::Scalar(s) => (a / s).into_lua(lua),
+      }
+    });
+    methods.add_meta_function(MetaMethod::Unm, |lua, a: Vec2| {
+      (-a).into_lua(lua)
+    });
+    methods.add_meta_function(MetaMethod::Eq, |_, (a, b): (Vec2, Vec2)| {
+      Ok(a == b)
+    });
+    methods.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+      let vec: Vec2 = this.get();
+      Ok(format!("Vec2({}, {})", vec.x, vec.y))
+    });
   }
 }
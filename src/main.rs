@@ -1,4 +1,3 @@
-
 use std::{env, error::Error};
 
 use macroquad::{window::Conf};
@@ -10,28 +9,83 @@ mod core;
 //? IDK HOW TO CHANGE ICON!!
 //TODO Animations, Physics Stuff, and maybe more Nodes that idk rn
 
-fn get_conf() -> Conf { 
-  let args: Vec<String> = env::args().collect();
-  let fname: &str = if args.len() <= 1 {
-    "main.lua"
-  } else {
-    args.get(1).unwrap()
-  };
-  WindowConfig::load(fname).expect(&format!("Cannot load {}", fname)).into()
+const DEFAULT_HEADLESS_DT: f32 = 1.0 / 60.0;
+
+struct Args {
+  fname: String,
+  headless: Option<u64>,
+  dt: f32,
+  inspect: Option<String>,
 }
 
-#[macroquad::main(get_conf)]
-async fn main() -> Result<(), Box<dyn Error>> {
-  let args: Vec<String> = env::args().collect();
-  let fname: &str = if args.len() <= 1 {
-    "main.lua"
-  } else {
-    args.get(1).unwrap()
-  };
-  
-  let mut engine: Engine = Engine::load(fname)?;
+fn parse_args() -> Args {
+  let mut args = Args { fname: "main.lua".to_string(), headless: None, dt: DEFAULT_HEADLESS_DT, inspect: None };
+  let mut iter = env::args().skip(1);
+  while let Some(arg) = iter.next() {
+    match arg.as_str() {
+      "--headless" => {
+        let frames = iter.next().expect("--headless expects a frame count");
+        args.headless = Some(frames.parse().expect("Invalid frame count"));
+      },
+      "--dt" => {
+        let dt = iter.next().expect("--dt expects a delta time in seconds");
+        args.dt = dt.parse().expect("Invalid delta time");
+      },
+      "--inspect" => {
+        args.inspect = Some(iter.next().expect("--inspect expects a node path"));
+      },
+      flag if flag.starts_with("--") => {
+        eprintln!("Error: unknown argument '{}'", flag);
+        std::process::exit(2);
+      },
+      _ => args.fname = arg,
+    }
+  }
+  args
+}
+
+fn get_conf() -> Conf {
+  let fname: String = parse_args().fname;
+  WindowConfig::load(&fname).expect(&format!("Cannot load {}", fname)).into()
+}
+
+fn run_headless(args: &Args, frames: u64) -> Result<(), Box<dyn Error>> {
+  let mut engine: Engine = Engine::load_headless(&args.fname)?;
+  let ran = engine.run_frames(frames, args.dt);
+  println!("Ran {} frames of {} headless", ran, args.fname);
+  match &args.inspect {
+    Some(path) => {
+      let node = engine.get_node(path).ok_or_else(|| format!("No node at '{}'", path))?;
+      println!("{} = {}", path, node.describe(engine.lua(), 0)?);
+    },
+    None => println!("root = {}", engine.root().describe(engine.lua(), 0)?),
+  }
+  Ok(())
+}
+
+async fn amain() -> Result<(), Box<dyn Error>> {
+  let fname: String = parse_args().fname;
+
+  let mut engine: Engine = Engine::load(&fname)?;
 
 
   engine.mainloop().await;
   Ok(())
 }
+
+fn main() {
+  let args: Args = parse_args();
+  if let Some(frames) = args.headless {
+    if let Err(err) = run_headless(&args, frames) {
+      eprintln!("Error: {}", err);
+      std::process::exit(1);
+    }
+    return;
+  }
+
+  macroquad::Window::from_config(get_conf(), async {
+    if let Err(err) = amain().await {
+      macroquad::logging::error!("Error: {:?}", err);
+    }
+  });
+}
@@ -0,0 +1,23 @@
+Size = {x = 320, y = 240}
+function character(name, pos)
+  add_node(name, CharacterBody(pos))
+  root[name].children.shape = Collider(Vec2(0, 0), Vec2(10, 10))
+end
+function Setup()
+  add_node("ground", Collider(Vec2(0, 100), Vec2(200, 20)))
+  character("faller", Vec2(50, 80))
+
+  add_node("room", Collider(Vec2(1000, 100), Vec2(400, 20)))
+  add_node("wall", Collider(Vec2(1200, 0), Vec2(20, 100)))
+  character("walker", Vec2(1180, 85))
+
+  add_node("slope", Collider(Vec2(2000, 100), Vec2(400, 20)))
+  root.slope.transform.rotation = -0.3
+  character("climber", Vec2(2100, 40))
+
+  add_node("ledge", Collider(Vec2(3000, 100), Vec2(200, 10)))
+  root.ledge.one_way = true
+  character("jumper", Vec2(3050, 115))
+end
+function Loop(dt)
+end
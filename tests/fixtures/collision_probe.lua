@@ -0,0 +1,9 @@
+function CollisionEnter(info)
+  this.log = (this.log or "") .. "enter;"
+end
+function CollisionStay(info)
+  this.log = (this.log or "") .. "stay;"
+end
+function CollisionExit(info)
+  this.log = (this.log or "") .. "exit;"
+end
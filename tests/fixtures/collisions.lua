@@ -0,0 +1,8 @@
+Size = {x = 320, y = 240}
+function Setup()
+  add_node("probe", embed("tests/fixtures/collision_probe.lua", Collider(Vec2(0, 0), Vec2(10, 10))))
+  add_node("block", Collider(Vec2(100, 0), Vec2(10, 10)))
+  add_node("mine", Collider(Vec2(200, 0), Vec2(10, 10)))
+end
+function Loop(dt)
+end
@@ -0,0 +1,16 @@
+Size = {x = 320, y = 240}
+PhysicsRate = 10
+function Setup()
+  add_node("glider", RectMesh(Vec2(0, 0), Vec2(10, 10), ColorRgb(255, 255, 255)))
+  add_node("mover", RectMesh(Vec2(0, 50), Vec2(10, 10), ColorRgb(255, 255, 255)))
+  ticks = 0
+  frames = 0
+end
+function FixedLoop(dt)
+  ticks = ticks + 1
+  root.glider.transform.pos = Vec2(ticks * 10, 0)
+end
+function Loop(dt)
+  frames = frames + 1
+  root.mover.transform.pos = Vec2(frames * 5, 50)
+end
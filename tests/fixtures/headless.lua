@@ -0,0 +1,10 @@
+Size = {x = 320, y = 240}
+function Setup()
+  add_node("player", RectMesh(Vec2(0, 0), Vec2(10, 10), ColorRgb(255, 255, 255)))
+  frames = 0
+end
+function Loop(dt)
+  frames = frames + 1
+  root.player.transform.pos = Vec2(frames * 2, 0)
+  if frames == 10 then quit() end
+end
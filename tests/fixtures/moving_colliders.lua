@@ -0,0 +1,9 @@
+Size = {x = 320, y = 240}
+function Setup()
+  add_node("wall", Collider(Vec2(200, 0), Vec2(10, 10)))
+  add_node("mover", Collider(Vec2(0, 0), Vec2(10, 10)))
+  add_node("rig", RectMesh(Vec2(0, 100), Vec2(1, 1), ColorRgb(0, 0, 0)))
+  root.rig.children.arm = Collider(Vec2(0, 0), Vec2(10, 10))
+end
+function Loop(dt)
+end
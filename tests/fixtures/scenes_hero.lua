@@ -0,0 +1,3 @@
+function Loop(dt)
+  this.transform.pos = this.transform.pos + Vec2(1, 0)
+end
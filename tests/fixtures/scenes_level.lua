@@ -0,0 +1,7 @@
+Gravity = {x = 0, y = 50}
+PhysicsRate = 30
+function Setup()
+  add_node("floor", RectMesh(Vec2(0, 200), Vec2(320, 10), ColorRgb(0, 0, 0)))
+end
+function Loop(dt)
+end
@@ -0,0 +1,10 @@
+Size = {x = 320, y = 240}
+Gravity = {x = 0, y = 100}
+function Setup()
+  add_node("hero", embed("tests/fixtures/scenes_hero.lua", RectMesh(Vec2(0, 0), Vec2(10, 10), ColorRgb(255, 255, 255))))
+  frames = 0
+end
+function Loop(dt)
+  frames = frames + 1
+  if frames == 2 then change_scene("tests/fixtures/scenes_level.lua") end
+end
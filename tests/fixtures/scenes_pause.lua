@@ -0,0 +1,4 @@
+function Setup()
+end
+function Loop(dt)
+end